@@ -0,0 +1,60 @@
+use std::time::Duration;
+
+use super::{Ariel, OUTPUT_DIR};
+use sebastian_core::ariel::forum::ArielExportFormat;
+
+/// Archive the discussions in the forums of a site.
+#[derive(clap::Parser, Clone, Debug)]
+pub(crate) struct Forum {
+    /// The format of the archived discussions: 'markdown' or 'json'.
+    #[clap(short, long, default_value_t)]
+    pub format: ArielExportFormat,
+
+    /// The base of the directory to save the discussions.
+    #[clap(short, long, default_value = &OUTPUT_DIR)]
+    pub output: String,
+
+    /// The URL of the home page of the site, or of one of its forums.
+    pub url: String,
+}
+
+impl Ariel {
+    pub(crate) async fn forum(
+        &mut self,
+        format: ArielExportFormat,
+        output: String,
+        url: String,
+    ) -> anyhow::Result<()> {
+        let pb = indicatif::ProgressBar::new_spinner();
+        pb.enable_steady_tick(Duration::from_millis(120));
+        pb.set_style(
+            indicatif::ProgressStyle::with_template("{prefix:.bold.dim} {spinner} {wide_msg}")
+                .unwrap()
+                .tick_chars("⠁⠂⠄⡀⢀⠠⠐⠈ "),
+        );
+        pb.set_message(format!("crawling forums from {}...", url));
+
+        let threads = self.nav.as_ref().unwrap().get_forum(url.clone()).await?;
+
+        pb.set_style(
+            indicatif::ProgressStyle::with_template("{prefix:.bold.dim} {wide_msg}").unwrap(),
+        );
+        if threads.is_empty() {
+            pb.set_prefix("x");
+            pb.finish_with_message(format!("no discussions found from {}!", url));
+            return Ok(());
+        }
+
+        for thread in &threads {
+            let path = thread.save(&output, format)?;
+            log::info!("saved '{}' to {:?}", thread.title, path);
+        }
+        pb.set_prefix("✓");
+        pb.finish_with_message(format!(
+            "archived {} discussions in {}",
+            threads.len(),
+            output
+        ));
+        Ok(())
+    }
+}
@@ -1,8 +1,11 @@
 use std::time::Duration;
 
-use sebastian_core::ariel::{ArielNavigator, ArielUserConfig};
+use sebastian_core::{
+    ariel::{ArielNavigator, ArielUserConfig},
+    password::SecretString,
+};
 
-use super::Ariel;
+use super::{session_path, Ariel};
 
 /// Log into Ariel and initialize the configuration.
 #[derive(clap::Parser, Clone, Debug)]
@@ -11,14 +14,14 @@ pub(crate) struct Login {
     pub username: Option<String>,
 
     #[clap(short, long)]
-    pub password: Option<String>,
+    pub password: Option<SecretString>,
 }
 
 impl Ariel {
     pub(crate) async fn login(
         &mut self,
         username: Option<String>,
-        password: Option<String>,
+        password: Option<SecretString>,
         auto: bool,
     ) -> anyhow::Result<()> {
         log::debug!("username: {:?}, password: {:?}", username, password);
@@ -64,14 +67,25 @@ impl Ariel {
             if auto {
                 pwd = password;
             } else {
-                pwd = inquire::Password::new("password:").prompt()?
+                pwd = inquire::Password::new("password:").prompt()?.into()
             }
         } else {
-            pwd = inquire::Password::new("password:").prompt()?
+            pwd = inquire::Password::new("password:").prompt()?.into()
         }
         let password = pwd;
-        let config = ArielUserConfig { username, password };
-        self.nav = Some(ArielNavigator::new(config.clone()));
+        // The credentials were just typed: make sure they are checked instead of
+        // reusing the session of the previous ones.
+        let session_path = session_path(&username);
+        if let Err(e) = std::fs::remove_file(&session_path) {
+            log::debug!("no session removed from {:?}: {}", session_path, e);
+        }
+        let config = ArielUserConfig {
+            username,
+            password,
+            session_path: Some(session_path),
+            ..self.user_config.clone().unwrap_or_default()
+        };
+        self.nav = Some(ArielNavigator::new(self.runtime_config(&config))?);
 
         let pb = indicatif::ProgressBar::new_spinner();
         pb.enable_steady_tick(Duration::from_millis(120));
@@ -97,8 +111,38 @@ impl Ariel {
                 pb.set_style(indicatif::ProgressStyle::with_template("").unwrap());
 
                 pb.finish();
-                anyhow::bail!("could not login with supplied username and password, {}", e)
+                match e {
+                    sebastian_core::Error::InvalidCredentials => {
+                        anyhow::bail!("could not login with supplied username and password")
+                    }
+                    e => anyhow::bail!("could not login: {}", e),
+                }
             }
         }
     }
+
+    /// Offer to keep the password just checked in the credential vault, where
+    /// it is saved on exit, rather than in clear text in the configuration.
+    pub(crate) fn offer_vault(&mut self) -> anyhow::Result<()> {
+        let silent = self.app_config.as_ref().unwrap().silent;
+        let config = self.user_config.as_mut().unwrap();
+        if config.credential.is_some() || config.password_from.is_some() {
+            return Ok(());
+        }
+        if silent {
+            log::warn!(
+                "the password is saved in clear text, use `credentials set --use-for ariel` to keep it in the vault"
+            );
+            return Ok(());
+        }
+
+        let mut keep = inquire::Confirm::new("keep the password in the encrypted vault?");
+        keep.default = Some(true);
+        if keep.prompt()? {
+            let mut name = inquire::Text::new("name of the password in the vault:");
+            name.default = Some("ariel");
+            config.credential = Some(name.prompt()?);
+        }
+        Ok(())
+    }
 }
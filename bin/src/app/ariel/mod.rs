@@ -1,12 +1,18 @@
+mod forum;
 mod login;
+mod network;
+mod news;
 mod scrape;
 mod search;
+mod tree;
 
-use super::{AppConfig, CURRENT_DIR};
+use std::path::PathBuf;
+
+use super::{AppConfig, CACHE_DIR, CURRENT_DIR};
 use lazy_static::lazy_static;
 use sebastian_core::{
     ariel::ArielUserConfig,
-    ariel::{map::ArielSitemap, ArielNavigator},
+    ariel::{search::ArielSearchFilter, ArielNavigator},
 };
 
 lazy_static! {
@@ -15,7 +21,6 @@ lazy_static! {
         c.push_str("/result");
         c
     };
-    static ref ARIEL_SITEMAP: ArielSitemap = ArielSitemap::default();
 }
 
 /// Access the Ariel website and search for content to scrape.
@@ -24,6 +29,9 @@ pub(crate) struct Ariel {
     #[clap(subcommand)]
     pub action: ArielAction,
 
+    #[clap(flatten)]
+    pub network: network::NetworkArgs,
+
     #[clap(skip)]
     pub user_config: Option<ArielUserConfig>,
 
@@ -39,6 +47,9 @@ pub(crate) enum ArielAction {
     Scrape(scrape::Scrape),
     Init(login::Login),
     Search(search::Search),
+    Tree(tree::Tree),
+    Forum(forum::Forum),
+    News(news::News),
 }
 
 impl Ariel {
@@ -59,19 +70,22 @@ impl Ariel {
         match self.action {
             ArielAction::Scrape(scrape::Scrape {
                 auto,
+                sync,
+                messages,
+                jobs,
                 ref output,
                 ref url,
+                ..
             }) => {
                 let output = output.clone();
                 let url = url.clone();
 
-                if let Some(cfg) = &self.user_config {
-                    self.nav = Some(ArielNavigator::new(cfg.clone()));
-                    self.nav.as_mut().unwrap().login().await?
+                if self.user_config.is_some() {
+                    self.connect().await?
                 } else {
                     self.login(None, None, false).await?;
                 };
-                self.scrape(auto, output, url).await?
+                self.scrape(auto, sync, messages, jobs, output, url).await?
             }
             ArielAction::Init(login::Login {
                 ref username,
@@ -83,18 +97,54 @@ impl Ariel {
                     None
                 };
                 self.login(username, password.clone(), false).await?;
+                self.offer_vault()?;
                 self.app_config.as_mut().unwrap().save = true;
             }
 
-            ArielAction::Search(search::Search { ref name }) => {
+            ArielAction::Search(search::Search {
+                ref teacher,
+                ref year,
+                access,
+                sort,
+                reverse,
+                jobs,
+                format,
+                ref name,
+            }) => {
                 let name = name.clone();
-                if let Some(cfg) = &self.user_config {
-                    self.nav = Some(ArielNavigator::new(cfg.clone()));
-                    self.nav.as_mut().unwrap().login().await?
-                } else {
-                    self.login(None, None, false).await?;
+                let filter = ArielSearchFilter {
+                    teacher: teacher.clone(),
+                    year: year.clone(),
+                    access,
                 };
-                self.search(name).await?
+                self.connect().await?;
+                self.search(name, filter, sort, reverse, jobs, format)
+                    .await?
+            }
+
+            ArielAction::Tree(tree::Tree {
+                ref output,
+                ref url,
+            }) => {
+                let (output, url) = (output.clone(), url.clone());
+                self.connect().await?;
+                self.tree(output, url).await?
+            }
+
+            ArielAction::Forum(forum::Forum {
+                format,
+                ref output,
+                ref url,
+            }) => {
+                let (output, url) = (output.clone(), url.clone());
+                self.connect().await?;
+                self.forum(format, output, url).await?
+            }
+
+            ArielAction::News(news::News { ref since, ref url }) => {
+                let (since, url) = (since.clone(), url.clone());
+                self.connect().await?;
+                self.news(since, url).await?
             }
         };
 
@@ -103,4 +153,39 @@ impl Ariel {
             self.user_config.as_ref().unwrap().clone(),
         ))
     }
+
+    /// `cfg` with the settings given on the command line for this run only,
+    /// which are not saved.
+    fn runtime_config(&self, cfg: &ArielUserConfig) -> ArielUserConfig {
+        let mut cfg = cfg.clone();
+        self.network.apply(&mut cfg.network);
+        if let ArielAction::Scrape(scrape) = &self.action {
+            scrape.apply(&mut cfg);
+        }
+        cfg
+    }
+
+    /// Log in with the configured credentials, reusing the saved session if
+    /// still valid, or ask for them if there are none.
+    async fn connect(&mut self) -> anyhow::Result<()> {
+        match self.user_config.as_mut() {
+            Some(cfg) => {
+                cfg.session_path = Some(session_path(&cfg.username));
+                let cfg = self.runtime_config(self.user_config.as_ref().unwrap());
+                self.nav = Some(ArielNavigator::new(cfg)?);
+                self.nav.as_mut().unwrap().login().await?;
+                Ok(())
+            }
+            None => self.login(None, None, false).await,
+        }
+    }
+}
+
+/// Where the session cookies of `username` are kept between runs.
+fn session_path(username: &str) -> PathBuf {
+    let name: String = username
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    PathBuf::from(CACHE_DIR.as_str()).join(format!("ariel-{}.cookies", name))
 }
@@ -0,0 +1,68 @@
+use std::path::PathBuf;
+
+use sebastian_core::net::NetworkConfig;
+
+/// Network settings overriding those in the configuration.
+#[derive(clap::Args, Clone, Debug, Default)]
+pub(crate) struct NetworkArgs {
+    /// An 'http://', 'https://' or 'socks5://' proxy for every request.
+    #[clap(long, global = true)]
+    pub proxy: Option<String>,
+
+    /// Seconds to wait for a connection to be established.
+    #[clap(long, global = true)]
+    pub connect_timeout: Option<u64>,
+
+    /// Seconds to wait for the server to answer before giving up.
+    #[clap(long, global = true)]
+    pub read_timeout: Option<u64>,
+
+    /// A PEM file with certificates to trust besides those of the system.
+    #[clap(long, global = true)]
+    pub ca_bundle: Option<PathBuf>,
+
+    /// The user agent sent with every request.
+    #[clap(long, global = true)]
+    pub user_agent: Option<String>,
+
+    /// How many requests to send each second at most.
+    #[clap(long, global = true)]
+    pub requests_per_second: Option<u32>,
+
+    /// How many requests can be waiting for an answer at the same time.
+    #[clap(long, global = true)]
+    pub max_concurrent_requests: Option<usize>,
+
+    /// How many bytes per second all the downloads together can take.
+    #[clap(long, global = true)]
+    pub max_download_rate: Option<u64>,
+}
+
+impl NetworkArgs {
+    pub(crate) fn apply(&self, network: &mut NetworkConfig) {
+        if let Some(proxy) = &self.proxy {
+            network.proxy = Some(proxy.clone());
+        }
+        if let Some(secs) = self.connect_timeout {
+            network.connect_timeout = Some(secs);
+        }
+        if let Some(secs) = self.read_timeout {
+            network.read_timeout = Some(secs);
+        }
+        if let Some(path) = &self.ca_bundle {
+            network.ca_bundle = Some(path.clone());
+        }
+        if let Some(user_agent) = &self.user_agent {
+            network.user_agent = Some(user_agent.clone());
+        }
+        if let Some(n) = self.requests_per_second {
+            network.requests_per_second = Some(n);
+        }
+        if let Some(n) = self.max_concurrent_requests {
+            network.max_concurrent_requests = Some(n);
+        }
+        if let Some(rate) = self.max_download_rate {
+            network.max_download_rate = Some(rate);
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use std::time::Duration;
+
+use super::Ariel;
+use sebastian_core::ariel::news;
+
+/// Show the latest announcements of your sites.
+#[derive(clap::Parser, Clone, Debug)]
+pub(crate) struct News {
+    /// Only show announcements published on this date ('YYYY-MM-DD' or
+    /// 'DD/MM/YYYY') or later.
+    #[clap(long)]
+    pub since: Option<String>,
+
+    /// The URL of the home page of a site, or of Ariel to show the
+    /// announcements of all your sites [default: the home page of Ariel].
+    pub url: Option<String>,
+}
+
+impl Ariel {
+    pub(crate) async fn news(
+        &mut self,
+        since: Option<String>,
+        url: Option<String>,
+    ) -> anyhow::Result<()> {
+        let nav = self.nav.as_ref().unwrap();
+        let url = url.unwrap_or_else(|| nav.sitemap().home_page_url.clone());
+        let since = match since {
+            Some(since) => match news::parse_date(&since) {
+                Some(since) => Some(since),
+                None => anyhow::bail!(
+                    "invalid date '{}', expected 'YYYY-MM-DD' or 'DD/MM/YYYY'",
+                    since
+                ),
+            },
+            None => None,
+        };
+
+        let pb = indicatif::ProgressBar::new_spinner();
+        pb.enable_steady_tick(Duration::from_millis(120));
+        pb.set_style(
+            indicatif::ProgressStyle::with_template("{prefix:.bold.dim} {spinner} {wide_msg}")
+                .unwrap()
+                .tick_chars("⠁⠂⠄⡀⢀⠠⠐⠈ "),
+        );
+        pb.set_message(format!("fetching announcements from {}...", url));
+
+        let mut news = nav.get_news(url.clone()).await?;
+        if let Some(since) = since {
+            news.retain(|n| n.is_since(&since));
+        }
+
+        pb.set_style(
+            indicatif::ProgressStyle::with_template("{prefix:.bold.dim} {wide_msg}").unwrap(),
+        );
+        if news.is_empty() {
+            pb.set_prefix("x");
+            pb.finish_with_message(format!("no announcements found from {}!", url));
+            return Ok(());
+        }
+        pb.set_style(indicatif::ProgressStyle::with_template("").unwrap());
+        pb.finish();
+
+        for n in news {
+            println!(
+                "[{}] {} - {}",
+                n.date.as_deref().unwrap_or("?"),
+                n.from_site,
+                n.title
+            );
+            for line in n.to_markdown().lines() {
+                println!("    {}", line);
+            }
+            println!();
+        }
+        Ok(())
+    }
+}
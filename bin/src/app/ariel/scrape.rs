@@ -1,8 +1,20 @@
-use std::{fmt::Write, thread, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+    path::Path,
+    thread,
+    time::Duration,
+};
 
 use super::Ariel;
 use crate::app::CURRENT_DIR;
-use sebastian_core::ariel::map::ArielSitemap;
+use sebastian_core::ariel::{
+    manifest::ArielManifest,
+    page::ArielPage,
+    queue::{ArielDownloadEvent, ArielDownloadQueue},
+    tool::{ArielTool, ArielToolKind},
+    ArielStreamContainer, ArielStreamQuality, ArielUserConfig,
+};
 
 lazy_static::lazy_static! {
     static ref OUTPUT_DIR: String = {
@@ -10,7 +22,6 @@ lazy_static::lazy_static! {
         c.push_str("/result");
         c
     };
-    static ref ARIEL_SITEMAP: ArielSitemap = ArielSitemap::default();
 }
 
 /// Perform scraping.
@@ -24,35 +35,102 @@ pub(crate) struct Scrape {
     #[clap(short, long, default_value = &OUTPUT_DIR)]
     pub output: String,
 
-    /// The URL of the page to start the scraping from.
-    #[clap(default_value = &ARIEL_SITEMAP.home_page_url)]
-    pub url: String,
+    /// Only download data that is new or changed since the last run.
+    #[clap(long)]
+    pub sync: bool,
+
+    /// Also save the text of the threads found as Markdown.
+    #[clap(short, long)]
+    pub messages: bool,
+
+    /// How many downloads to run in parallel.
+    #[clap(short, long, default_value_t = 4)]
+    pub jobs: usize,
+
+    /// The quality of lesson streams: 'highest', 'lowest', a maximum height
+    /// such as '720p' or a maximum bandwidth such as '2000kbps'.
+    #[clap(short, long)]
+    pub quality: Option<ArielStreamQuality>,
+
+    /// Remux lesson streams into a seekable 'mp4' or 'mkv' file.
+    #[clap(long)]
+    pub remux: Option<ArielStreamContainer>,
+
+    /// Keep lesson streams as they are, even if remuxing is configured.
+    #[clap(long, conflicts_with = "remux")]
+    pub no_remux: bool,
+
+    /// Compute the exact size of lesson streams before downloading them,
+    /// instead of estimating it (slower).
+    #[clap(long)]
+    pub exact_size: bool,
+
+    /// The URL of the page to start the scraping from [default: the home page
+    /// of Ariel].
+    pub url: Option<String>,
+}
+
+impl Scrape {
+    /// Apply the stream settings given for this run to `cfg`.
+    pub(crate) fn apply(&self, cfg: &mut ArielUserConfig) {
+        if let Some(quality) = self.quality {
+            cfg.stream_quality = quality;
+        }
+        if self.remux.is_some() {
+            cfg.remux = self.remux;
+        }
+        if self.no_remux {
+            cfg.remux = None;
+        }
+        if self.exact_size {
+            cfg.exact_stream_size = true;
+        }
+    }
 }
 
 impl Ariel {
+    fn unknown_tools(page: &ArielPage) -> Vec<ArielTool> {
+        if !page.is_site_home() {
+            return vec![];
+        }
+        page.get_tools()
+            .into_iter()
+            .filter(|t| t.kind == ArielToolKind::Unknown)
+            .collect()
+    }
+
     pub(crate) async fn scrape(
         &mut self,
         auto: bool,
+        sync: bool,
+        save_messages: bool,
+        jobs: usize,
         out_path: String,
-        url: String,
+        url: Option<String>,
     ) -> anyhow::Result<()> {
-        let page = self
-            .nav
-            .as_mut()
-            .unwrap()
-            .page_from_url(url.clone())
-            .await?;
+        let nav = self.nav.as_mut().unwrap();
+        let url = url.unwrap_or_else(|| nav.sitemap().home_page_url.clone());
+        let page = nav.page_from_url(url.clone()).await?;
         log::debug!("page: {:?}", page);
         let mut to_ask = page.get_data();
+        let mut messages = page.get_messages();
+        let mut unknown_tools = Self::unknown_tools(&page);
+        // Pages link back to each other: each one is only offered once.
+        let mut visited = HashSet::from([page.url.clone()]);
 
         if auto {
             let mut stack = self.nav.as_mut().unwrap().get_children(page).await;
+            stack.retain(|c| visited.insert(c.url.clone()));
 
             while stack.len() != 0 {
                 let child_page = stack.pop().unwrap();
                 log::info!("getting data from child {}", child_page.url);
                 to_ask.append(&mut child_page.get_data());
-                stack.append(&mut self.nav.as_mut().unwrap().get_children(child_page).await);
+                messages.append(&mut child_page.get_messages());
+                unknown_tools.append(&mut Self::unknown_tools(&child_page));
+                let mut children = self.nav.as_mut().unwrap().get_children(child_page).await;
+                children.retain(|c| visited.insert(c.url.clone()));
+                stack.append(&mut children);
             }
         } else {
             let mut stack = vec![];
@@ -65,7 +143,8 @@ impl Ariel {
             );
             pb.set_message(format!("searching pages from {}...", url));
 
-            let children = self.nav.as_mut().unwrap().get_children(page).await;
+            let mut children = self.nav.as_mut().unwrap().get_children(page).await;
+            children.retain(|c| visited.insert(c.url.clone()));
 
             pb.set_style(indicatif::ProgressStyle::with_template("").unwrap());
             pb.finish();
@@ -78,6 +157,8 @@ impl Ariel {
                 let child_page = stack.pop().unwrap();
                 log::info!("getting data from child {}", child_page.url);
                 to_ask.append(&mut child_page.get_data());
+                messages.append(&mut child_page.get_messages());
+                unknown_tools.append(&mut Self::unknown_tools(&child_page));
 
                 let pb = indicatif::ProgressBar::new_spinner();
                 pb.enable_steady_tick(Duration::from_millis(120));
@@ -89,7 +170,8 @@ impl Ariel {
                     .tick_chars("⠁⠂⠄⡀⢀⠠⠐⠈ "),
                 );
                 pb.set_message(format!("searching pages from {}...", child_page.url));
-                let children = self.nav.as_mut().unwrap().get_children(child_page).await;
+                let mut children = self.nav.as_mut().unwrap().get_children(child_page).await;
+                children.retain(|c| visited.insert(c.url.clone()));
                 pb.set_message(format!("searching pages from {}...", url));
                 pb.set_style(indicatif::ProgressStyle::with_template("").unwrap());
 
@@ -104,11 +186,64 @@ impl Ariel {
             }
         }
 
+        if !unknown_tools.is_empty() {
+            let pb = indicatif::ProgressBar::new_spinner();
+            pb.set_style(
+                indicatif::ProgressStyle::with_template("{prefix:.bold.dim} {wide_msg}").unwrap(),
+            );
+            pb.set_prefix("!");
+            pb.finish_with_message(format!(
+                "found tools of unknown kind, their data may be missing: {}",
+                unknown_tools
+                    .iter()
+                    .map(|t| t.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+
+        if save_messages {
+            for message in &messages {
+                match message.save(&out_path) {
+                    Ok(path) => log::info!("saved message '{}' to {:?}", message.title, path),
+                    Err(e) => log::warn!("could not save message '{}': {}", message.title, e),
+                }
+            }
+        }
+
         if to_ask.is_empty() {
+            if save_messages && !messages.is_empty() {
+                return Ok(());
+            }
             anyhow::bail!("Found no data to scrape!")
         }
 
-        let selected = inquire::MultiSelect::new("Select data to scrape: ", to_ask).prompt()?;
+        let out_dir = Path::new(&out_path);
+        let mut manifest = ArielManifest::load(out_dir)?;
+
+        let queue = ArielDownloadQueue::new(self.nav.clone().unwrap(), jobs);
+
+        let selected = if sync {
+            let changed = queue.changed(&manifest, to_ask).await;
+
+            if changed.is_empty() {
+                let pb = indicatif::ProgressBar::new_spinner();
+                pb.set_style(
+                    indicatif::ProgressStyle::with_template("{prefix:.bold.dim} {wide_msg}")
+                        .unwrap(),
+                );
+                pb.set_prefix("✓");
+                pb.finish_with_message(format!("everything in {} is up to date!", out_path));
+                return Ok(());
+            }
+            changed
+        } else {
+            inquire::MultiSelect::new("Select data to scrape: ", to_ask)
+                .prompt()?
+                .into_iter()
+                .map(|d| (d, None))
+                .collect()
+        };
 
         if selected.len() == 0 {
             anyhow::bail!("No data selected!")
@@ -136,44 +271,92 @@ impl Ariel {
         .progress_chars(progs)
         .tick_strings(&ticks);
 
-        let mut chunk_done_size_cx;
-        let mut chunk_done_size_px;
+        let (events_px, events_cx) = std::sync::mpsc::channel::<ArielDownloadEvent>();
+        let data = selected.iter().map(|(d, _)| d.clone()).collect::<Vec<_>>();
+        let progress_out_path = out_path.clone();
 
-        for d in selected {
-            let size = self.nav.as_mut().unwrap().get_size(&d).await?;
-            let style = if size != 0 {
-                sized_bar_style.clone()
-            } else {
-                unsized_bar_style.clone()
-            };
-            let name = d.get_name();
+        let c = thread::spawn(move || {
+            let bars = indicatif::MultiProgress::new();
+            let mut active = HashMap::new();
 
-            (chunk_done_size_px, chunk_done_size_cx) = std::sync::mpsc::channel::<u64>();
+            for event in events_cx {
+                match event {
+                    ArielDownloadEvent::Started { index, name, size } => {
+                        let style = if size != 0 {
+                            sized_bar_style.clone()
+                        } else {
+                            unsized_bar_style.clone()
+                        };
+                        let pb = bars.add(indicatif::ProgressBar::new(size));
+                        pb.set_style(style);
+                        pb.set_position(0);
+                        pb.set_message(format!("fetch {}", name));
+                        active.insert(index, (pb, name, size));
+                    }
+                    ArielDownloadEvent::Progress { index, bytes } => {
+                        if let Some((pb, _, size)) = active.get(&index) {
+                            pb.inc(bytes);
+                            if pb.position() > *size {
+                                pb.set_length(pb.position())
+                            }
+                        }
+                    }
+                    ArielDownloadEvent::Finished {
+                        index,
+                        path,
+                        version,
+                    } => {
+                        if let Some((pb, name, _)) = active.remove(&index) {
+                            pb.finish_with_message(format!("downloaded {}", name));
+                        }
 
-            let c = thread::spawn(move || {
-                let pb = indicatif::ProgressBar::new(size);
-                let mut chunk_bytes: u64 = 0;
-
-                pb.set_style(style.clone());
-                pb.set_position(0);
-                pb.set_message(format!("fetch {}", name));
-
-                for bs in chunk_done_size_cx {
-                    chunk_bytes += bs;
-                    pb.set_position(chunk_bytes.try_into().unwrap());
-                    pb.set_length(chunk_bytes)
+                        let d = &data[index];
+                        match std::fs::metadata(&path) {
+                            Ok(meta) => {
+                                manifest.record(d, path, meta.len(), version);
+                                if let Err(e) = manifest.save(Path::new(&progress_out_path)) {
+                                    log::warn!("could not save manifest: {}", e);
+                                }
+                            }
+                            Err(e) => log::warn!("could not stat {:?}: {}", path, e),
+                        }
+                    }
+                    ArielDownloadEvent::Failed { index, error } => {
+                        if let Some((pb, name, _)) = active.remove(&index) {
+                            pb.abandon_with_message(format!("failed {}: {}", name, error));
+                        }
+                    }
                 }
+            }
+        });
 
-                pb.finish_with_message(format!("downloaded {}", name));
-            });
-
-            self.nav
-                .as_mut()
-                .unwrap()
-                .download(out_path.clone(), d.clone(), chunk_done_size_px)
-                .await?;
+        let results = queue.run(out_path.clone(), selected, events_px).await;
+        tokio::task::spawn_blocking(move || c.join())
+            .await?
+            .expect("child panicked");
 
-            c.join().expect("child panicked");
+        let retries = self.nav.as_ref().unwrap().retries();
+        let failed = results
+            .iter()
+            .filter_map(|(d, res)| res.as_ref().err().map(|e| format!("{} ({})", d, e)))
+            .collect::<Vec<_>>();
+        if !failed.is_empty() {
+            anyhow::bail!(
+                "could not download {} ({} requests retried)",
+                failed.join(", "),
+                retries
+            )
+        }
+        if retries > 0 {
+            let pb = indicatif::ProgressBar::new_spinner();
+            pb.set_style(
+                indicatif::ProgressStyle::with_template("{prefix:.bold.dim} {wide_msg}").unwrap(),
+            );
+            pb.set_prefix("!");
+            pb.finish_with_message(format!(
+                "downloaded everything, {} requests retried",
+                retries
+            ));
         }
 
         Ok(())
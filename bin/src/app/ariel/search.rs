@@ -1,15 +1,57 @@
 use super::{Ariel, OUTPUT_DIR};
-use std::time::Duration;
+use sebastian_core::ariel::{
+    page::ArielTitlePage,
+    search::{ArielAccessFilter, ArielSearchFilter, ArielSearchSort},
+};
+use std::{io::IsTerminal, time::Duration};
 
 /// Search course pages to scrape.
 #[derive(clap::Parser, Clone, Debug)]
 pub(crate) struct Search {
+    /// Only show courses held by a teacher whose name contains this.
+    #[clap(short, long)]
+    pub teacher: Option<String>,
+
+    /// Only show courses of this academic year, such as '2022' or '2021/2022'.
+    #[clap(short, long)]
+    pub year: Option<String>,
+
+    /// Which courses to show: 'accessible', 'closed' or 'any'.
+    #[clap(short, long, default_value_t)]
+    pub access: ArielAccessFilter,
+
+    /// Sort courses by 'title', 'year', 'code' or 'teacher'.
+    #[clap(long, default_value_t)]
+    pub sort: ArielSearchSort,
+
+    /// Reverse the order of the courses.
+    #[clap(short, long)]
+    pub reverse: bool,
+
+    /// How many downloads to run in parallel when scraping the selected
+    /// courses.
+    #[clap(short, long, default_value_t = 4)]
+    pub jobs: usize,
+
+    /// How to print the selected courses [default: table]. When given, or
+    /// when nobody can answer prompts, every course found is printed.
+    #[clap(short, long, value_enum)]
+    pub format: Option<SearchFormat>,
+
     /// The identifier of the course to scrape.
     pub name: String,
 }
 
 impl Ariel {
-    pub(crate) async fn search(&mut self, name: String) -> anyhow::Result<()> {
+    pub(crate) async fn search(
+        &mut self,
+        name: String,
+        filter: ArielSearchFilter,
+        sort: ArielSearchSort,
+        reverse: bool,
+        jobs: usize,
+        format: Option<SearchFormat>,
+    ) -> anyhow::Result<()> {
         log::info!("search '{}'", name);
         if self.nav.is_none() || self.user_config.is_none() {
             anyhow::bail!("cannot use uninitialized subcommand!")
@@ -25,7 +67,11 @@ impl Ariel {
         pb.set_message(format!("searching courses for '{}'...", name.clone()));
 
         let pages = self.nav.as_mut().unwrap().search(name.as_str()).await?;
-        let pages = pages.iter().filter(|p| p.can_access).collect::<Vec<_>>();
+        let mut pages = filter.apply(pages);
+        sort.sort(&mut pages);
+        if reverse {
+            pages.reverse();
+        }
 
         pb.set_style(
             indicatif::ProgressStyle::with_template("{prefix:.bold.dim} {wide_msg}").unwrap(),
@@ -41,6 +87,16 @@ impl Ariel {
             pb.set_style(indicatif::ProgressStyle::with_template("").unwrap());
             pb.finish();
         }
+
+        // Scripts and pipes get the results straight away.
+        let interactive = !self.app_config.as_ref().unwrap().silent
+            && std::io::stdin().is_terminal()
+            && std::io::stdout().is_terminal();
+        if format.is_some() || !interactive {
+            print!("{}", format.unwrap_or_default().render(&pages)?);
+            return Ok(());
+        }
+
         let ans = inquire::MultiSelect::new("Select the courses to search:", pages).prompt()?;
         if ans.len() == 0 {
             anyhow::bail!("No course selected!")
@@ -48,12 +104,196 @@ impl Ariel {
         let action = inquire::Select::new("Select action", vec!["scrape", "print"]).prompt()?;
         if action == "scrape" {
             for page in ans {
-                self.scrape(true, OUTPUT_DIR.to_string(), page.url.to_string())
-                    .await?;
+                self.scrape(
+                    true,
+                    false,
+                    false,
+                    jobs,
+                    OUTPUT_DIR.to_string(),
+                    Some(page.url.to_string()),
+                )
+                .await?;
             }
         } else {
+            print!("{}", format.unwrap_or_default().render(&ans)?);
         }
 
         Ok(())
     }
 }
+
+/// The output formats of the "print" action.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default)]
+pub(crate) enum SearchFormat {
+    /// An aligned table.
+    #[default]
+    Table,
+    Json,
+    Csv,
+}
+
+/// A search result, as printed.
+#[derive(serde::Serialize)]
+struct SearchRow {
+    title: String,
+    url: String,
+    teachers: Vec<String>,
+    accessible: bool,
+}
+
+impl From<&ArielTitlePage> for SearchRow {
+    fn from(page: &ArielTitlePage) -> Self {
+        SearchRow {
+            title: page.title.clone(),
+            url: page.url.to_string(),
+            teachers: page.holders.iter().map(|h| h.name.clone()).collect(),
+            accessible: page.can_access,
+        }
+    }
+}
+
+impl SearchRow {
+    const HEADER: [&'static str; 4] = ["title", "url", "teachers", "accessible"];
+
+    fn cells(&self) -> [String; 4] {
+        [
+            self.title.clone(),
+            self.url.clone(),
+            self.teachers.join("; "),
+            self.accessible.to_string(),
+        ]
+    }
+}
+
+impl SearchFormat {
+    fn render(&self, pages: &[ArielTitlePage]) -> anyhow::Result<String> {
+        let rows = pages.iter().map(SearchRow::from).collect::<Vec<_>>();
+        let mut out = String::new();
+
+        match self {
+            SearchFormat::Json => {
+                out.push_str(&serde_json::to_string_pretty(&rows)?);
+                out.push('\n');
+            }
+            SearchFormat::Csv => {
+                let lines = std::iter::once(SearchRow::HEADER.map(String::from))
+                    .chain(rows.iter().map(SearchRow::cells));
+                for cells in lines {
+                    let cells = cells.iter().map(|c| csv_field(c)).collect::<Vec<_>>();
+                    out.push_str(&cells.join(","));
+                    out.push('\n');
+                }
+            }
+            SearchFormat::Table => {
+                let header = SearchRow::HEADER.map(|h| h.to_uppercase());
+                let cells = rows.iter().map(SearchRow::cells).collect::<Vec<_>>();
+                let mut widths = header.clone().map(|h| h.chars().count());
+                for row in &cells {
+                    for (width, cell) in widths.iter_mut().zip(row) {
+                        *width = (*width).max(cell.chars().count());
+                    }
+                }
+
+                for row in std::iter::once(&header).chain(&cells) {
+                    let line = row
+                        .iter()
+                        .zip(widths)
+                        .map(|(cell, width)| format!("{:width$}", cell, width = width))
+                        .collect::<Vec<_>>();
+                    out.push_str(line.join("  ").trim_end());
+                    out.push('\n');
+                }
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// Quote `field` if needed, as per RFC 4180.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sebastian_core::ariel::page::ArielTeacher;
+
+    fn pages() -> Vec<ArielTitlePage> {
+        let teacher = |id: &str, name: &str| ArielTeacher {
+            id: id.to_string(),
+            name: name.to_string(),
+            url: format!("https://ariel.unimi.it/offerta/teacher/{}", id)
+                .parse()
+                .unwrap(),
+        };
+        vec![
+            ArielTitlePage {
+                title: "Algebra, \"lineare\"\ne geometria".to_string(),
+                url: "https://algebra.ariel.ctu.unimi.it/v5/home/Default.aspx"
+                    .parse()
+                    .unwrap(),
+                holders: vec![
+                    teacher("mario-rossi", "Mario Rossi"),
+                    teacher("anna-bianchi", "Anna Bianchi"),
+                ],
+                can_access: true,
+                academic_year: None,
+                degree: None,
+                code: None,
+            },
+            ArielTitlePage {
+                title: "Fisica".to_string(),
+                url: "https://fisica.ariel.ctu.unimi.it/v5/home/Default.aspx"
+                    .parse()
+                    .unwrap(),
+                holders: vec![],
+                can_access: false,
+                academic_year: None,
+                degree: None,
+                code: None,
+            },
+        ]
+    }
+
+    #[test]
+    fn csv_quotes_fields_that_need_it() {
+        let csv = SearchFormat::Csv.render(&pages()).unwrap();
+        assert_eq!(
+            csv,
+            "title,url,teachers,accessible\n\
+             \"Algebra, \"\"lineare\"\"\ne geometria\",\
+             https://algebra.ariel.ctu.unimi.it/v5/home/Default.aspx,\
+             Mario Rossi; Anna Bianchi,true\n\
+             Fisica,https://fisica.ariel.ctu.unimi.it/v5/home/Default.aspx,,false\n"
+        );
+    }
+
+    #[test]
+    fn json_lists_every_teacher() {
+        let json = SearchFormat::Json.render(&pages()).unwrap();
+        let rows = serde_json::from_str::<serde_json::Value>(&json).unwrap();
+        assert_eq!(
+            rows,
+            serde_json::json!([
+                {
+                    "title": "Algebra, \"lineare\"\ne geometria",
+                    "url": "https://algebra.ariel.ctu.unimi.it/v5/home/Default.aspx",
+                    "teachers": ["Mario Rossi", "Anna Bianchi"],
+                    "accessible": true,
+                },
+                {
+                    "title": "Fisica",
+                    "url": "https://fisica.ariel.ctu.unimi.it/v5/home/Default.aspx",
+                    "teachers": [],
+                    "accessible": false,
+                },
+            ])
+        );
+        assert!(json.ends_with("]\n"));
+    }
+}
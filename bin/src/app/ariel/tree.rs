@@ -0,0 +1,40 @@
+use std::time::Duration;
+
+use super::Ariel;
+
+/// Print the structure of a course site (ambients, threads and attachments)
+/// as JSON.
+#[derive(clap::Parser, Clone, Debug)]
+pub(crate) struct Tree {
+    /// Write the tree to this file instead of the standard output.
+    #[clap(short, long)]
+    pub output: Option<String>,
+
+    /// The URL of the home page of the site.
+    pub url: String,
+}
+
+impl Ariel {
+    pub(crate) async fn tree(&mut self, output: Option<String>, url: String) -> anyhow::Result<()> {
+        let pb = indicatif::ProgressBar::new_spinner();
+        pb.enable_steady_tick(Duration::from_millis(120));
+        pb.set_style(
+            indicatif::ProgressStyle::with_template("{prefix:.bold.dim} {spinner} {wide_msg}")
+                .unwrap()
+                .tick_chars("⠁⠂⠄⡀⢀⠠⠐⠈ "),
+        );
+        pb.set_message(format!("crawling {}...", url));
+
+        let site = self.nav.as_ref().unwrap().get_site(url).await?;
+
+        pb.set_style(indicatif::ProgressStyle::with_template("").unwrap());
+        pb.finish();
+
+        let json = serde_json::to_string_pretty(&site)?;
+        match output {
+            Some(output) => std::fs::write(output, json)?,
+            None => println!("{}", json),
+        }
+        Ok(())
+    }
+}
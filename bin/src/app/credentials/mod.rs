@@ -0,0 +1,239 @@
+use std::path::Path;
+
+use sebastian_core::{
+    password::{PasswordSource, SecretString},
+    vault::CredentialVault,
+};
+use zeroize::Zeroizing;
+
+use super::{AppConfig, UserConfig, VAULT_PATH};
+
+/// Manage the passwords kept in the encrypted credential vault.
+#[derive(clap::Parser, Debug)]
+pub(crate) struct Credentials {
+    #[clap(subcommand)]
+    pub action: CredentialsAction,
+}
+
+#[derive(clap::Parser, Clone, Debug)]
+pub(crate) enum CredentialsAction {
+    /// Store a password in the vault.
+    Set {
+        /// The name of the password.
+        name: String,
+
+        /// Use the password for a service instead of the one in the configuration.
+        #[clap(long, value_enum)]
+        use_for: Option<Service>,
+    },
+    /// Remove a password from the vault.
+    Rm {
+        /// The name of the password.
+        name: String,
+    },
+    /// List the names of the passwords in the vault.
+    List,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub(crate) enum Service {
+    Ariel,
+    Unimia,
+}
+
+/// The credential vault once unlocked, with the secret needed to save it again.
+pub(crate) struct UnlockedVault {
+    pub vault: CredentialVault,
+    secret: Zeroizing<Vec<u8>>,
+}
+
+impl UnlockedVault {
+    /// Unlock the vault with the key file, or else with a passphrase asked to
+    /// the user, twice if the vault is yet to be created.
+    pub(crate) fn unlock(app_config: &AppConfig) -> anyhow::Result<UnlockedVault> {
+        let path = Path::new(VAULT_PATH.as_str());
+        let secret = if let Some(key_file) = &app_config.key_file {
+            Zeroizing::new(std::fs::read(key_file)?)
+        } else if app_config.silent {
+            anyhow::bail!("the credential vault can only be unlocked with --key-file when silent")
+        } else {
+            let passphrase = Zeroizing::new(inquire::Password::new("vault passphrase:").prompt()?);
+            if !path.exists() {
+                let repeated = Zeroizing::new(
+                    inquire::Password::new("repeat the vault passphrase:").prompt()?,
+                );
+                if repeated != passphrase {
+                    anyhow::bail!("the passphrases do not match")
+                }
+            }
+            Zeroizing::new(passphrase.as_bytes().to_vec())
+        };
+
+        let vault = CredentialVault::open(path, &secret)?;
+        Ok(UnlockedVault { vault, secret })
+    }
+
+    pub(crate) fn save(&self) -> anyhow::Result<()> {
+        self.vault
+            .save(Path::new(VAULT_PATH.as_str()), &self.secret)?;
+        Ok(())
+    }
+}
+
+/// The passwords of `config` kept in the vault, with the name of their entry.
+fn vaulted_passwords(config: &mut UserConfig) -> Vec<(String, &mut SecretString)> {
+    let mut res = vec![];
+    if let Some(ariel) = config.ariel.as_mut() {
+        if let Some(name) = &ariel.credential {
+            res.push((name.clone(), &mut ariel.password));
+        }
+    }
+    if let Some(mia) = config.mia.as_mut() {
+        if let Some(name) = &mia.credential {
+            res.push((name.clone(), &mut mia.password));
+        }
+    }
+    res
+}
+
+/// The passwords of `config` read from elsewhere at every run, with where
+/// they are read from.
+fn sourced_passwords(config: &mut UserConfig) -> Vec<(PasswordSource, &mut SecretString)> {
+    let mut res = vec![];
+    if let Some(ariel) = config.ariel.as_mut() {
+        if let Some(source) = &ariel.password_from {
+            res.push((source.clone(), &mut ariel.password));
+        }
+    }
+    if let Some(mia) = config.mia.as_mut() {
+        if let Some(source) = &mia.password_from {
+            res.push((source.clone(), &mut mia.password));
+        }
+    }
+    res
+}
+
+/// Fill in the password of `service` if `config` reads it from a source or
+/// keeps it in the vault, unlocking the vault only then.
+pub(crate) fn fill_password(
+    config: &mut UserConfig,
+    service: Service,
+    app_config: &AppConfig,
+) -> anyhow::Result<Option<UnlockedVault>> {
+    let (credential, password_from, password) = match service {
+        Service::Ariel => match config.ariel.as_mut() {
+            Some(c) => (&c.credential, &c.password_from, &mut c.password),
+            None => return Ok(None),
+        },
+        Service::Unimia => match config.mia.as_mut() {
+            Some(c) => (&c.credential, &c.password_from, &mut c.password),
+            None => return Ok(None),
+        },
+    };
+    if credential.is_some() && password_from.is_some() {
+        anyhow::bail!("a password can be read either from the vault or from a source, not both")
+    }
+
+    if let Some(source) = password_from {
+        *password = source.resolve()?;
+    }
+
+    let name = match credential {
+        Some(name) => name,
+        None => return Ok(None),
+    };
+    let unlocked = UnlockedVault::unlock(app_config)?;
+    match unlocked.vault.get(name) {
+        Some(stored) => *password = stored.clone(),
+        None => anyhow::bail!("there is no password named '{}' in the vault", name),
+    }
+    Ok(Some(unlocked))
+}
+
+/// Remove from `config` the passwords read from a source or kept in the vault,
+/// so that they are not saved in clear text, storing those that changed in the
+/// vault first (unlocking it if needed).
+pub(crate) fn strip_passwords(
+    config: &mut UserConfig,
+    unlocked: &mut Option<UnlockedVault>,
+    app_config: &AppConfig,
+) -> anyhow::Result<()> {
+    let mut passwords = vaulted_passwords(config);
+    if unlocked.is_none() && passwords.iter().any(|(_, p)| !p.is_empty()) {
+        *unlocked = Some(UnlockedVault::unlock(app_config)?);
+    }
+    if let Some(unlocked) = unlocked {
+        let mut changed = false;
+        for (name, password) in passwords.iter() {
+            if !password.is_empty() && unlocked.vault.get(name) != Some(&**password) {
+                unlocked.vault.set(name, (**password).clone());
+                changed = true;
+            }
+        }
+        if changed {
+            unlocked.save()?;
+        }
+    }
+    for (_, password) in passwords.iter_mut() {
+        password.clear();
+    }
+    for (_, password) in sourced_passwords(config) {
+        password.clear();
+    }
+    Ok(())
+}
+
+impl Credentials {
+    pub(crate) async fn run(
+        &mut self,
+        mut app_config: AppConfig,
+        config: &mut UserConfig,
+        unlocked: &mut Option<UnlockedVault>,
+    ) -> anyhow::Result<AppConfig> {
+        if unlocked.is_none() {
+            *unlocked = Some(UnlockedVault::unlock(&app_config)?);
+        }
+        let unlocked = unlocked.as_mut().unwrap();
+
+        match &self.action {
+            CredentialsAction::Set { name, use_for } => {
+                let password = SecretString::from(inquire::Password::new("password:").prompt()?);
+                unlocked.vault.set(name, password.clone());
+                unlocked.save()?;
+
+                match use_for {
+                    Some(Service::Ariel) => {
+                        let ariel = config.ariel.get_or_insert_with(Default::default);
+                        ariel.credential = Some(name.clone());
+                        ariel.password = password;
+                    }
+                    Some(Service::Unimia) => {
+                        let mia = config.mia.get_or_insert_with(Default::default);
+                        mia.credential = Some(name.clone());
+                        mia.password = password;
+                    }
+                    None => {}
+                }
+                if use_for.is_some() {
+                    app_config.save = true;
+                }
+            }
+            CredentialsAction::Rm { name } => {
+                if !unlocked.vault.remove(name) {
+                    anyhow::bail!("there is no password named '{}' in the vault", name)
+                }
+                unlocked.save()?;
+                if vaulted_passwords(config).iter().any(|(n, _)| n == name) {
+                    log::warn!("'{}' is still used by the configuration", name);
+                }
+            }
+            CredentialsAction::List => {
+                for name in unlocked.vault.names() {
+                    println!("{}", name);
+                }
+            }
+        }
+
+        Ok(app_config)
+    }
+}
@@ -1,4 +1,5 @@
 pub(crate) mod ariel;
+pub(crate) mod credentials;
 pub(crate) mod time;
 pub(crate) mod unimia;
 
@@ -18,6 +19,17 @@ lazy_static! {
         .join("config")
         .to_string_lossy()
         .to_string();
+    static ref VAULT_PATH: String = ProjectDirs::from("", "", "sebastian")
+        .unwrap()
+        .config_dir()
+        .join("credentials.vault")
+        .to_string_lossy()
+        .to_string();
+    static ref CACHE_DIR: String = ProjectDirs::from("", "", "sebastian")
+        .unwrap()
+        .cache_dir()
+        .to_string_lossy()
+        .to_string();
     static ref CURRENT_DIR: String = std::env::current_dir()
         .unwrap()
         .into_os_string()
@@ -49,6 +61,10 @@ pub(crate) struct App {
     #[clap(long, long, global = true)]
     pub silent: bool,
 
+    /// A file whose content unlocks the credential vault, instead of a passphrase.
+    #[clap(long, global = true)]
+    pub key_file: Option<String>,
+
     #[clap(subcommand)]
     pub action: Command,
 }
@@ -59,6 +75,7 @@ impl App {
             config_path: self.config_path.clone(),
             save: self.save,
             silent: self.silent,
+            key_file: self.key_file.clone(),
         }
     }
 }
@@ -71,11 +88,14 @@ pub(crate) struct AppConfig {
     pub save: bool,
 
     pub silent: bool,
+
+    pub key_file: Option<String>,
 }
 
 #[derive(clap::Parser, Debug)]
 pub(crate) enum Command {
     Ariel(ariel::Ariel),
+    Credentials(credentials::Credentials),
     Time(time::Time),
     Unimia(unimia::Unimia),
 }
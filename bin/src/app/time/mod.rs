@@ -21,7 +21,6 @@ pub enum TimeTableAction {
     Show,
 }
 
-
 #[allow(unused)]
 impl Time {
     pub(crate) async fn run(
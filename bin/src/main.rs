@@ -1,6 +1,6 @@
 use std::{fs::OpenOptions, io::Write, path::Path};
 
-use app::{Command, UserConfig};
+use app::{credentials, Command, UserConfig};
 use clap::Parser;
 mod app;
 
@@ -24,6 +24,18 @@ async fn main() -> anyhow::Result<()> {
     };
 
     log::trace!("config is {:?}", config);
+    // Only the password of the service used is needed. The credentials
+    // command unlocks the vault by itself, and must work even when the
+    // configuration refers to a password not stored yet.
+    let mut unlocked = match app.action {
+        Command::Ariel(_) => {
+            credentials::fill_password(&mut config, credentials::Service::Ariel, &app_config)?
+        }
+        Command::Unimia(_) => {
+            credentials::fill_password(&mut config, credentials::Service::Unimia, &app_config)?
+        }
+        Command::Credentials(_) | Command::Time(_) => None,
+    };
 
     let app_config = match app.action {
         Command::Ariel(mut a) => {
@@ -32,6 +44,10 @@ async fn main() -> anyhow::Result<()> {
             config.ariel = Some(ariel_config);
             app_config
         }
+        Command::Credentials(mut c) => {
+            log::debug!("subcommand is credentials");
+            c.run(app_config, &mut config, &mut unlocked).await?
+        }
         Command::Time(mut t) => {
             log::debug!("subcommand is time");
             let (app_config, time_config) = t.run(app_config, config.time).await?;
@@ -48,6 +64,7 @@ async fn main() -> anyhow::Result<()> {
 
     if app_config.save {
         log::debug!("saving config");
+        credentials::strip_passwords(&mut config, &mut unlocked, &app_config)?;
         let maybe_path;
         if !app_config.silent {
             let mut path = inquire::Text::new("save to config in path:");
@@ -0,0 +1,137 @@
+use std::path::PathBuf;
+
+use url::Url;
+
+use super::{page::fnv1a, tree::ArielAttachment};
+use crate::Error;
+
+/// A discussion in a forum room of a site, with all its replies.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ArielForumThread {
+    pub from_site: String,
+    pub from_room: String,
+    pub title: String,
+    pub url: Url,
+    pub posts: Vec<ArielForumPost>,
+}
+
+/// A single message of a forum thread, the first one being the question.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ArielForumPost {
+    pub title: String,
+    pub author: Option<String>,
+    pub date: Option<String>,
+    /// The text of the post being replied to, as HTML, if quoted.
+    pub quote: Option<String>,
+    /// The body of the post, as HTML.
+    pub body: String,
+    pub attachments: Vec<ArielAttachment>,
+}
+
+/// How forum threads are exported.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArielExportFormat {
+    #[default]
+    Markdown,
+    Json,
+}
+
+impl ArielExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ArielExportFormat::Markdown => "md",
+            ArielExportFormat::Json => "json",
+        }
+    }
+}
+
+impl std::str::FromStr for ArielExportFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "md" | "markdown" => Ok(ArielExportFormat::Markdown),
+            "json" => Ok(ArielExportFormat::Json),
+            _ => Err(Error::InvalidValue {
+                value: s.to_string(),
+                expected: "'markdown' or 'json'".to_string(),
+            }),
+        }
+    }
+}
+
+impl std::fmt::Display for ArielExportFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArielExportFormat::Markdown => write!(f, "markdown"),
+            ArielExportFormat::Json => write!(f, "json"),
+        }
+    }
+}
+
+impl ArielForumThread {
+    pub fn to_markdown(&self) -> String {
+        let mut md = format!("# {}\n\n", self.title);
+        for post in &self.posts {
+            md.push_str(&format!("## {}\n\n", post.title));
+            match (&post.author, &post.date) {
+                (Some(author), Some(date)) => md.push_str(&format!("*{}, {}*\n\n", author, date)),
+                (Some(line), None) | (None, Some(line)) => md.push_str(&format!("*{}*\n\n", line)),
+                (None, None) => {}
+            }
+            if let Some(quote) = &post.quote {
+                for line in html2md::parse_html(quote).trim().lines() {
+                    match line.trim_end() {
+                        "" => md.push_str(">\n"),
+                        line => md.push_str(&format!("> {}\n", line)),
+                    }
+                }
+                md.push('\n');
+            }
+            let body = html2md::parse_html(&post.body);
+            if !body.trim().is_empty() {
+                md.push_str(body.trim());
+                md.push_str("\n\n");
+            }
+            for attachment in &post.attachments {
+                md.push_str(&format!("- [{}]({})\n", attachment.name, attachment.url));
+            }
+            if !post.attachments.is_empty() {
+                md.push('\n');
+            }
+        }
+        md.truncate(md.trim_end().len());
+        md.push('\n');
+        md
+    }
+
+    /// Where the thread is exported in `format` under `base`, told apart
+    /// from threads with the same title by a hash of its URL.
+    pub fn get_path(&self, base: &str, format: ArielExportFormat) -> PathBuf {
+        let mut path = PathBuf::from(base);
+        path.push(heck::AsSnakeCase(&self.from_site).to_string());
+        path.push(heck::AsSnakeCase(&self.from_room).to_string());
+        path.push(format!(
+            "{}_{:016x}.{}",
+            heck::AsSnakeCase(&self.title),
+            fnv1a(self.url.as_str().as_bytes()),
+            format.extension()
+        ));
+        path
+    }
+
+    /// Export the thread in `format` under `base`, returning its path.
+    pub fn save(&self, base: &str, format: ArielExportFormat) -> crate::Result<PathBuf> {
+        let path = self.get_path(base, format);
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        let content = match format {
+            ArielExportFormat::Markdown => self.to_markdown(),
+            ArielExportFormat::Json => {
+                serde_json::to_string_pretty(self).map_err(std::io::Error::from)?
+            }
+        };
+        std::fs::write(&path, content)?;
+        Ok(path)
+    }
+}
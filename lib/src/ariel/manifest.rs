@@ -0,0 +1,123 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use super::page::ArielPageData;
+use crate::Error;
+
+/// What the server tells about an item, to find out whether it changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ArielRemoteVersion {
+    /// The size in bytes, 0 if unknown.
+    pub size: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<String>,
+}
+
+/// A single downloaded item, as recorded in an [`ArielManifest`].
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ArielManifestEntry {
+    pub url: String,
+    pub path: PathBuf,
+    pub size: u64,
+    /// Seconds since the UNIX epoch.
+    pub fetched_at: u64,
+    /// What the server told about the item when it was downloaded.
+    #[serde(default)]
+    pub remote: ArielRemoteVersion,
+}
+
+/// The list of items already downloaded into an output directory.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct ArielManifest {
+    entries: HashMap<String, ArielManifestEntry>,
+}
+
+impl ArielManifest {
+    pub const FILE_NAME: &'static str = ".sebastian-manifest.json";
+
+    pub fn load(dir: &Path) -> crate::Result<ArielManifest> {
+        let path = dir.join(Self::FILE_NAME);
+        if !path.exists() {
+            log::debug!("no manifest in {:?}, starting from an empty one", dir);
+            return Ok(ArielManifest::default());
+        }
+        let raw = std::fs::read_to_string(&path)?;
+        serde_json::from_str(&raw).map_err(|e| Error::Parse {
+            page: path.to_string_lossy().to_string(),
+            reason: e.to_string(),
+        })
+    }
+
+    pub fn save(&self, dir: &Path) -> crate::Result<()> {
+        std::fs::create_dir_all(dir)?;
+        let path = dir.join(Self::FILE_NAME);
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(
+            &tmp,
+            serde_json::to_string_pretty(self).map_err(std::io::Error::from)?,
+        )?;
+        std::fs::rename(tmp, path)?;
+        Ok(())
+    }
+
+    pub fn get(&self, data: &ArielPageData) -> Option<&ArielManifestEntry> {
+        self.entries.get(data.url.as_str())
+    }
+
+    /// Whether `data` was already fetched and is unchanged, judging by its
+    /// ETag or its modification date when the server sends them, and by the
+    /// size the server told otherwise. That is compared to the size the server
+    /// told last time rather than to the local copy, since for streams it is
+    /// only an estimate. A size of 0 means it is unknown, and then only the
+    /// local copy is checked.
+    pub fn is_up_to_date(&self, data: &ArielPageData, remote: &ArielRemoteVersion) -> bool {
+        let entry = match self.get(data) {
+            Some(entry) => entry,
+            None => return false,
+        };
+
+        let local_size = match std::fs::metadata(&entry.path) {
+            Ok(meta) => meta.len(),
+            Err(_) => return false,
+        };
+
+        if local_size != entry.size {
+            return false;
+        }
+        if let (Some(etag), Some(known)) = (&remote.etag, &entry.remote.etag) {
+            return etag == known;
+        }
+        if let (Some(date), Some(known)) = (&remote.last_modified, &entry.remote.last_modified) {
+            return date == known;
+        }
+        remote.size == 0 || remote.size == entry.remote.size
+    }
+
+    pub fn record(
+        &mut self,
+        data: &ArielPageData,
+        path: PathBuf,
+        size: u64,
+        remote: ArielRemoteVersion,
+    ) {
+        let fetched_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        self.entries.insert(
+            data.url.to_string(),
+            ArielManifestEntry {
+                url: data.url.to_string(),
+                path,
+                size,
+                fetched_at,
+                remote,
+            },
+        );
+    }
+}
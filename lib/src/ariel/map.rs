@@ -1,16 +1,41 @@
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+/// Where Ariel is. Every field can be overridden in the configuration, to use
+/// a mirror, a local server or a new hostname.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 pub struct ArielSitemap {
     pub login_url: String,
     pub search_url: String,
     pub home_page_url: String,
+    /// The hosts serving the sites of the courses, which are also matched by
+    /// their subdomains.
+    pub site_hosts: Vec<String>,
 }
 
 impl Default for ArielSitemap {
     fn default() -> ArielSitemap {
         ArielSitemap {
-            login_url: "https://elearning.unimi.it/authentication/skin/portaleariel/login.aspx?url=https://ariel.unimi.it/".to_string(), 
+            login_url: "https://elearning.unimi.it/authentication/skin/portaleariel/login.aspx?url=https://ariel.unimi.it/".to_string(),
             search_url:  "https://ariel.unimi.it/offerta/search/quick".to_string(),
-            home_page_url: "https://ariel.unimi.it/".to_string()
+            home_page_url: "https://ariel.unimi.it/".to_string(),
+            site_hosts: vec!["ariel.ctu.unimi.it".to_string()],
         }
     }
 }
+
+impl ArielSitemap {
+    pub fn is_default(&self) -> bool {
+        *self == ArielSitemap::default()
+    }
+
+    /// Whether `url` points to the site of a course.
+    pub fn is_site_url(&self, url: &str) -> bool {
+        let url = match url.parse::<url::Url>() {
+            Ok(url) => url,
+            Err(_) => return false,
+        };
+        let host = url.host_str().unwrap_or_default();
+        self.site_hosts
+            .iter()
+            .any(|h| host == h || host.ends_with(&format!(".{}", h)))
+    }
+}
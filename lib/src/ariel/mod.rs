@@ -1,47 +1,211 @@
+use std::sync::Arc;
+
+use crate::{net::NetworkConfig, password::SecretString, Error};
+
 use self::{
+    forum::ArielForumThread,
+    manifest::ArielRemoteVersion,
+    map::ArielSitemap,
     mware::{http::HttpArielMiddleware, ArielMiddleware},
+    news::ArielAnnouncement,
     page::{ArielPage, ArielPageData, ArielTitlePage},
+    tree::{ArielAmbient, ArielSite},
 };
 
+pub mod forum;
+pub mod manifest;
 pub mod map;
 pub mod mware;
+pub mod news;
 pub mod page;
+pub mod queue;
+pub mod search;
+pub mod tool;
+pub mod tree;
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
 pub struct ArielUserConfig {
     pub username: String,
-    pub password: String,
+    #[serde(default, skip_serializing_if = "SecretString::is_empty")]
+    pub password: SecretString,
+    /// The name of the entry of the credential vault holding the password,
+    /// which is then never saved in the configuration.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credential: Option<String>,
+    /// Where to read the password from at every run, which is then never
+    /// saved in the configuration.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_from: Option<crate::password::PasswordSource>,
+    #[serde(default)]
+    pub stream_quality: ArielStreamQuality,
+    #[serde(default)]
+    pub remux: Option<ArielStreamContainer>,
+    /// Compute the size of lesson streams with a HEAD request per segment,
+    /// instead of estimating it from their playlist.
+    #[serde(default)]
+    pub exact_stream_size: bool,
+    /// Where to keep the session cookies between runs, so that a new login is
+    /// only needed when the session expires. Never saved with the rest of the
+    /// configuration.
+    #[serde(skip)]
+    pub session_path: Option<std::path::PathBuf>,
+    /// Where Ariel is, when not at its usual address.
+    #[serde(default, skip_serializing_if = "ArielSitemap::is_default")]
+    pub sitemap: ArielSitemap,
+    #[serde(default, skip_serializing_if = "NetworkConfig::is_default")]
+    pub network: NetworkConfig,
+}
+
+/// Which variant of a lesson stream to download when more are available.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum ArielStreamQuality {
+    #[default]
+    Highest,
+    Lowest,
+    /// The best variant not taller than the given height, e.g. `720p`.
+    MaxHeight(u64),
+    /// The best variant not exceeding the given bits per second, e.g. `2000kbps`.
+    MaxBandwidth(u64),
+}
+
+impl std::str::FromStr for ArielStreamQuality {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        let parsed = match s.as_str() {
+            "highest" => Some(ArielStreamQuality::Highest),
+            "lowest" => Some(ArielStreamQuality::Lowest),
+            _ => {
+                if let Some(height) = s.strip_suffix('p') {
+                    height.parse().ok().map(ArielStreamQuality::MaxHeight)
+                } else if let Some(kbps) = s.strip_suffix("kbps") {
+                    kbps.parse::<u64>()
+                        .ok()
+                        .map(|kbps| ArielStreamQuality::MaxBandwidth(kbps * 1000))
+                } else if let Some(bps) = s.strip_suffix("bps") {
+                    bps.parse().ok().map(ArielStreamQuality::MaxBandwidth)
+                } else {
+                    None
+                }
+            }
+        };
+        parsed.ok_or_else(|| Error::InvalidValue {
+            value: s,
+            expected: "'highest', 'lowest', '<height>p' or '<n>[k]bps'".to_string(),
+        })
+    }
+}
+
+impl std::fmt::Display for ArielStreamQuality {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArielStreamQuality::Highest => write!(f, "highest"),
+            ArielStreamQuality::Lowest => write!(f, "lowest"),
+            ArielStreamQuality::MaxHeight(height) => write!(f, "{}p", height),
+            ArielStreamQuality::MaxBandwidth(bps) => write!(f, "{}bps", bps),
+        }
+    }
 }
 
-#[derive(Debug)]
+impl TryFrom<String> for ArielStreamQuality {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<ArielStreamQuality> for String {
+    fn from(quality: ArielStreamQuality) -> Self {
+        quality.to_string()
+    }
+}
+
+/// The container lesson streams are remuxed into once downloaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArielStreamContainer {
+    Mp4,
+    Mkv,
+}
+
+impl ArielStreamContainer {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ArielStreamContainer::Mp4 => "mp4",
+            ArielStreamContainer::Mkv => "mkv",
+        }
+    }
+}
+
+impl std::str::FromStr for ArielStreamContainer {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "mp4" => Ok(ArielStreamContainer::Mp4),
+            "mkv" => Ok(ArielStreamContainer::Mkv),
+            _ => Err(Error::InvalidValue {
+                value: s.to_string(),
+                expected: "'mp4' or 'mkv'".to_string(),
+            }),
+        }
+    }
+}
+
+impl std::fmt::Display for ArielStreamContainer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.extension())
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct ArielNavigator {
-    middleware: Box<dyn ArielMiddleware>,
+    middleware: Arc<dyn ArielMiddleware>,
 }
 
 impl ArielNavigator {
-    pub fn new(config: ArielUserConfig) -> Self {
+    pub fn new(config: ArielUserConfig) -> crate::Result<Self> {
+        Ok(ArielNavigator {
+            middleware: Arc::new(HttpArielMiddleware::new(config)?),
+        })
+    }
+
+    /// Make a navigator that goes through `middleware` instead of the real Ariel.
+    pub fn with_middleware<M: ArielMiddleware + 'static>(middleware: M) -> Self {
         ArielNavigator {
-            middleware: Box::new(HttpArielMiddleware::new(config)),
+            middleware: Arc::new(middleware),
         }
     }
 
-    pub async fn login(&mut self) -> anyhow::Result<()> {
+    pub async fn login(&self) -> crate::Result<()> {
         self.middleware.login().await
     }
 
-    pub async fn search(&mut self, course_name: &str) -> anyhow::Result<Vec<ArielTitlePage>> {
+    pub fn sitemap(&self) -> &ArielSitemap {
+        self.middleware.sitemap()
+    }
+
+    /// How many requests were tried again after failing, so far.
+    pub fn retries(&self) -> u64 {
+        self.middleware.retries()
+    }
+
+    pub async fn search(&self, course_name: &str) -> crate::Result<Vec<ArielTitlePage>> {
         log::info!("passing '{}' to middleware", course_name);
         self.middleware.search(course_name).await
     }
 
-    pub async fn page_from_url(&mut self, url: String) -> anyhow::Result<ArielPage> {
+    pub async fn page_from_url(&self, url: String) -> crate::Result<ArielPage> {
         let (url, raw) = self.middleware.get(url.clone()).await?;
         log::debug!("making page from raw for url {}", url);
         ArielPage::from_raw(raw, url)
     }
 
-    pub async fn get_children(&mut self, page: ArielPage) -> Vec<ArielPage> {
-        let children_urls = page.get_children();
+    pub async fn get_children(&self, page: ArielPage) -> Vec<ArielPage> {
+        let children_urls = page.get_children(self.middleware.sitemap());
         if children_urls.len() == 1 && children_urls[0] == format!("{}v5", page.url.clone()) {
             let url = children_urls[0].clone();
             if let Ok((url, raw)) = self.middleware.get(url).await {
@@ -66,18 +230,106 @@ impl ArielNavigator {
         res
     }
 
-    pub async fn download<'a>(
-        &mut self,
+    /// Crawl the site at `url` down to its attachments.
+    pub async fn get_site(&self, url: String) -> crate::Result<ArielSite> {
+        let page = self.page_from_url(url).await?;
+        let mut site = ArielSite {
+            title: page.get_site_name(),
+            url: page.url.parse()?,
+            tools: page.get_tools(),
+            ambients: vec![],
+        };
+
+        let mut visited = std::collections::HashSet::from([page.url.clone()]);
+        let mut queue = std::collections::VecDeque::from(self.get_children(page).await);
+        queue.retain(|c| visited.insert(c.url.clone()));
+        while let Some(page) = queue.pop_front() {
+            log::info!("crawling {}", page.url);
+            let data = page.get_data();
+            let messages = page.get_messages();
+            if !data.is_empty() || !messages.is_empty() {
+                site.ambients.push(ArielAmbient::from_data(
+                    page.get_title(),
+                    page.url.parse()?,
+                    data,
+                    messages,
+                ));
+            }
+            let children = self.get_children(page).await;
+            queue.extend(
+                children
+                    .into_iter()
+                    .filter(|c| visited.insert(c.url.clone())),
+            );
+        }
+        Ok(site)
+    }
+
+    /// Crawl the forums reachable from `url`, the home page of a site or a
+    /// page of one of its forums, collecting all their discussions.
+    pub async fn get_forum(&self, url: String) -> crate::Result<Vec<ArielForumThread>> {
+        let page = self.page_from_url(url).await?;
+        let mut res = vec![];
+
+        let mut visited = std::collections::HashSet::from([page.url.clone()]);
+        let mut queue = std::collections::VecDeque::from(vec![page]);
+        while let Some(page) = queue.pop_front() {
+            log::info!("crawling {}", page.url);
+            if let Some(thread) = page.get_forum_thread() {
+                res.push(thread);
+            }
+            let children = self.get_children(page).await;
+            queue.extend(
+                children
+                    .into_iter()
+                    .filter(|c| c.is_forum() && visited.insert(c.url.clone())),
+            );
+        }
+        Ok(res)
+    }
+
+    /// Collect the announcements of the site at `url`, or of all the sites
+    /// listed in it if it is the Ariel home page, newest first.
+    pub async fn get_news(&self, url: String) -> crate::Result<Vec<ArielAnnouncement>> {
+        let page = self.page_from_url(url).await?;
+        let mut res = vec![];
+
+        let mut visited = std::collections::HashSet::from([page.url.clone()]);
+        let mut queue = std::collections::VecDeque::from(vec![page]);
+        while let Some(page) = queue.pop_front() {
+            log::info!("crawling {}", page.url);
+            res.append(&mut page.get_announcements());
+            if page.is_news() {
+                continue;
+            }
+            let children = self.get_children(page).await;
+            queue.extend(
+                children
+                    .into_iter()
+                    .filter(|c| (c.is_site_home() || c.is_news()) && visited.insert(c.url.clone())),
+            );
+        }
+
+        news::sort_newest_first(&mut res);
+        Ok(res)
+    }
+
+    pub async fn download(
+        &self,
         path: String,
         data: ArielPageData,
-        chunk_done_size_chan: std::sync::mpsc::Sender<u64>,
-    ) -> anyhow::Result<()> {
+        chunk_done_size_chan: tokio::sync::mpsc::UnboundedSender<u64>,
+    ) -> crate::Result<std::path::PathBuf> {
         self.middleware
             .download(path, data, chunk_done_size_chan)
             .await
     }
 
-    pub async fn get_size<'a>(&mut self, data: &'a ArielPageData) -> anyhow::Result<u64> {
-        self.middleware.get_size(data).await
+    pub async fn get_size(&self, data: &ArielPageData) -> crate::Result<u64> {
+        Ok(self.middleware.get_version(data).await?.size)
+    }
+
+    pub async fn get_version(&self, data: &ArielPageData) -> crate::Result<ArielRemoteVersion> {
+        self.middleware.get_version(data).await
     }
 }
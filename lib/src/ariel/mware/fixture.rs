@@ -0,0 +1,150 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use url::Url;
+
+use super::ArielMiddleware;
+use crate::ariel::{
+    manifest::ArielRemoteVersion,
+    map::ArielSitemap,
+    page::{ArielPageData, ArielPageDataKind, ArielSearchPage, ArielTitlePage},
+    ArielUserConfig,
+};
+use crate::Error;
+
+/// A middleware that never touches the network, serving pages and files saved
+/// in a directory instead. Lesson streams are not supported: to test them,
+/// serve the directory over HTTP to an
+/// [`HttpArielMiddleware`](super::http::HttpArielMiddleware).
+///
+/// An URL is looked up in the `index.json` file of the directory, a map from
+/// URLs to paths relative to it; URLs not in the index are looked up in
+/// `<host>/<path>`, with `index.html` standing for paths ending in `/`.
+#[derive(Debug)]
+pub struct FixtureArielMiddleware {
+    sitemap: ArielSitemap,
+    root: PathBuf,
+    index: HashMap<String, PathBuf>,
+}
+
+impl FixtureArielMiddleware {
+    pub fn with_root<P: AsRef<Path>>(config: ArielUserConfig, root: P) -> Self {
+        let root = root.as_ref().to_path_buf();
+        let index = match std::fs::read_to_string(root.join("index.json")) {
+            Ok(raw) => serde_json::from_str(&raw).unwrap_or_else(|e| {
+                log::warn!("invalid fixture index in {:?}: {}", root, e);
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        };
+
+        FixtureArielMiddleware {
+            sitemap: config.sitemap,
+            root,
+            index,
+        }
+    }
+
+    fn path_of(&self, url: &str) -> crate::Result<PathBuf> {
+        if let Some(path) = self.index.get(url) {
+            return Ok(self.root.join(path));
+        }
+
+        let parsed = url.parse::<Url>()?;
+        let mut path = self.root.join(parsed.host_str().unwrap_or_default());
+        for segment in parsed.path().split('/').filter(|s| !s.is_empty()) {
+            path.push(segment);
+        }
+        if parsed.path().ends_with('/') {
+            path.push("index.html");
+        }
+        Ok(path)
+    }
+
+    fn read(&self, url: &str) -> crate::Result<Vec<u8>> {
+        let path = self.path_of(url)?;
+        log::debug!("serving {} from {:?}", url, path);
+        match std::fs::read(&path) {
+            Ok(bytes) => Ok(bytes),
+            Err(e) => Err(std::io::Error::new(
+                e.kind(),
+                format!("no fixture for url '{}' ({:?}: {})", url, path, e),
+            )
+            .into()),
+        }
+    }
+
+    /// The content of the file `data` points to.
+    fn read_file(&self, data: &ArielPageData) -> crate::Result<Vec<u8>> {
+        match data.kind {
+            ArielPageDataKind::Generic => self.read(data.url.as_str()),
+            ArielPageDataKind::LessonStream => Err(Error::Stream {
+                url: data.url.to_string(),
+                reason: "lesson streams are not served by fixtures".to_string(),
+            }),
+        }
+    }
+}
+
+#[async_trait]
+impl ArielMiddleware for FixtureArielMiddleware {
+    fn new(config: ArielUserConfig) -> crate::Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(FixtureArielMiddleware::with_root(config, "fixtures"))
+    }
+
+    fn sitemap(&self) -> &ArielSitemap {
+        &self.sitemap
+    }
+
+    async fn login(&self) -> crate::Result<()> {
+        Ok(())
+    }
+
+    async fn search(&self, _: &str) -> crate::Result<Vec<ArielTitlePage>> {
+        let (url, raw) = self.get(self.sitemap.search_url.clone()).await?;
+        ArielSearchPage::title_pages(&url, raw)
+    }
+
+    async fn get(&self, url: String) -> crate::Result<(String, String)> {
+        let raw = String::from_utf8(self.read(&url)?).map_err(|e| Error::Parse {
+            page: url.clone(),
+            reason: e.to_string(),
+        })?;
+        Ok((url, raw))
+    }
+
+    async fn post(&self, url: String, _: Vec<(String, String)>) -> crate::Result<(String, String)> {
+        self.get(url).await
+    }
+
+    async fn is_logged_in(&self) -> crate::Result<()> {
+        Ok(())
+    }
+
+    async fn download<'a>(
+        &self,
+        path: String,
+        data: ArielPageData,
+        chunk_done_size_chan: tokio::sync::mpsc::UnboundedSender<u64>,
+    ) -> crate::Result<PathBuf> {
+        let bytes = self.read_file(&data)?;
+        let path = data.get_path(&path);
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        std::fs::write(&path, &bytes)?;
+        chunk_done_size_chan.send(bytes.len().try_into().unwrap())?;
+        Ok(path)
+    }
+
+    async fn get_version<'a>(&self, data: &'a ArielPageData) -> crate::Result<ArielRemoteVersion> {
+        Ok(ArielRemoteVersion {
+            size: self.read_file(data)?.len() as u64,
+            ..ArielRemoteVersion::default()
+        })
+    }
+}
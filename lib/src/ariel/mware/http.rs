@@ -1,47 +1,105 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use async_trait::async_trait;
+use futures::StreamExt;
 use reqwest::Client;
+use tokio::io::AsyncWriteExt;
 
-use super::ArielMiddleware;
+use m3u8_rs::{Key, KeyMethod};
+
+use super::{m3u8, remux, ArielMiddleware};
 use crate::ariel::{
+    manifest::ArielRemoteVersion,
     map::ArielSitemap,
     page::{ArielLoginPage, ArielPageData, ArielTitlePage},
     ArielUserConfig,
 };
+use crate::{
+    net::{RateLimiter, RequestTurn},
+    Error,
+};
 
-#[derive(Debug)]
 pub struct HttpArielMiddleware {
-    config: ArielUserConfig,
+    pub(super) config: ArielUserConfig,
     sitemap: ArielSitemap,
     cookies: std::sync::Arc<reqwest_cookie_store::CookieStoreMutex>,
+    /// Whether the cookies were restored from a previous session that has
+    /// not been checked yet.
+    restored: std::sync::atomic::AtomicBool,
+    /// How many requests were tried again.
+    retries: std::sync::atomic::AtomicU64,
+    limiter: RateLimiter,
     client: Client,
 }
 
+impl std::fmt::Debug for HttpArielMiddleware {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HttpArielMiddleware")
+            .field("config", &self.config)
+            .field("sitemap", &self.sitemap)
+            .field("cookies", &self.redacted_cookies())
+            .field("restored", &self.restored)
+            .field("retries", &self.retries)
+            .field("limiter", &self.limiter)
+            .finish_non_exhaustive()
+    }
+}
+
 #[async_trait]
 impl ArielMiddleware for HttpArielMiddleware {
-    fn new(config: ArielUserConfig) -> Self
+    fn new(config: ArielUserConfig) -> crate::Result<Self>
     where
         Self: Sized,
     {
-        let cookies = std::sync::Arc::new(reqwest_cookie_store::CookieStoreMutex::default());
-        let user_agent = String::from("Sebastian");
-        let client = reqwest::Client::builder()
+        let restored = config.session_path.as_deref().and_then(Self::load_session);
+        let is_restored = restored.is_some();
+        let cookies = std::sync::Arc::new(reqwest_cookie_store::CookieStoreMutex::new(
+            restored.unwrap_or_default(),
+        ));
+        let client = config
+            .network
+            .client_builder()?
             .cookie_store(true)
             .cookie_provider(cookies.clone())
-            .user_agent(user_agent)
             .redirect(reqwest::redirect::Policy::limited(15))
             .build()
-            .unwrap();
-        HttpArielMiddleware {
+            .map_err(|e| Error::NetworkSettings(e.to_string()))?;
+        Ok(HttpArielMiddleware {
+            sitemap: config.sitemap.clone(),
+            limiter: RateLimiter::new(&config.network),
             config,
-            sitemap: ArielSitemap::default(),
             cookies,
+            restored: std::sync::atomic::AtomicBool::new(is_restored),
+            retries: std::sync::atomic::AtomicU64::new(0),
             client,
-        }
+        })
     }
 
-    async fn login(&mut self) -> anyhow::Result<()> {
+    fn sitemap(&self) -> &ArielSitemap {
+        &self.sitemap
+    }
+
+    fn retries(&self) -> u64 {
+        self.retries.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    async fn login(&self) -> crate::Result<()> {
+        if self
+            .restored
+            .swap(false, std::sync::atomic::Ordering::SeqCst)
+        {
+            match self.is_logged_in().await {
+                Ok(()) => {
+                    log::info!("reusing the saved session");
+                    return Ok(());
+                }
+                Err(e) => {
+                    log::info!("the saved session is not valid anymore: {}", e);
+                    *self.cookies.lock().unwrap() = cookie_store::CookieStore::default();
+                }
+            }
+        }
+
         log::info!("logging in...");
         let (_, text) = self
             .post(
@@ -49,47 +107,54 @@ impl ArielMiddleware for HttpArielMiddleware {
                 vec![
                     ("hdnSilent".into(), "true".into()),
                     ("tbLogin".into(), self.config.username.clone()),
-                    ("tbPassword".into(), self.config.password.clone()),
+                    ("tbPassword".into(), self.config.password.expose().into()),
                 ],
             )
             .await?;
 
-        ArielLoginPage::is_logged_in(text)
+        ArielLoginPage::is_logged_in(text)?;
+        if let Err(e) = self.save_session() {
+            log::warn!("could not save the session: {}", e);
+        }
+        Ok(())
     }
 
-    async fn search(&mut self, course_name: &str) -> anyhow::Result<Vec<ArielTitlePage>> {
+    async fn search(&self, course_name: &str) -> crate::Result<Vec<ArielTitlePage>> {
         let res = self
             .post(
                 self.sitemap.search_url.clone(),
                 vec![("keyword".into(), course_name.into())],
             )
             .await?;
-        Ok(crate::ariel::page::ArielSearchPage::title_pages(res.1))
+        crate::ariel::page::ArielSearchPage::title_pages(&res.0, res.1)
     }
 
-    async fn get(&mut self, url: String) -> anyhow::Result<(String, String)> {
+    async fn get(&self, url: String) -> crate::Result<(String, String)> {
         self.get(url).await
     }
 
     async fn post(
-        &mut self,
+        &self,
         url: String,
         form: Vec<(String, String)>,
-    ) -> anyhow::Result<(String, String)> {
+    ) -> crate::Result<(String, String)> {
         self.post(url, form).await
     }
 
-    async fn is_logged_in(&mut self) -> anyhow::Result<()> {
+    async fn is_logged_in(&self) -> crate::Result<()> {
         let (_, page) = self.get(self.sitemap.home_page_url.clone()).await?;
-        crate::ariel::page::ArielLoginPage::is_logged_in(page)
+        if ArielLoginPage::is_login_page_raw(&page.clone().into()) {
+            return Err(Error::SessionExpired);
+        }
+        Ok(())
     }
 
     async fn download<'a>(
-        &mut self,
+        &self,
         path: String,
         data: ArielPageData,
-        chunk_done_size_chan: std::sync::mpsc::Sender<u64>,
-    ) -> anyhow::Result<()> {
+        chunk_done_size_chan: tokio::sync::mpsc::UnboundedSender<u64>,
+    ) -> crate::Result<PathBuf> {
         match data.kind {
             crate::ariel::page::ArielPageDataKind::LessonStream => {
                 self.download_stream(path, data, chunk_done_size_chan).await
@@ -101,197 +166,635 @@ impl ArielMiddleware for HttpArielMiddleware {
         }
     }
 
-    async fn get_size<'a>(&mut self, data: &'a ArielPageData) -> anyhow::Result<u64> {
+    async fn get_version<'a>(&self, data: &'a ArielPageData) -> crate::Result<ArielRemoteVersion> {
         match data.kind {
-            crate::ariel::page::ArielPageDataKind::LessonStream => {
-                self.get_size_of_stream(data.url.clone()).await
-            }
+            crate::ariel::page::ArielPageDataKind::LessonStream => Ok(ArielRemoteVersion {
+                size: self.get_size_of_stream(data.url.clone()).await?,
+                ..ArielRemoteVersion::default()
+            }),
             crate::ariel::page::ArielPageDataKind::Generic => {
-                self.get_size_generic(data.url.clone()).await
+                let (version, _) = self.head_generic(data.url.clone()).await?;
+                Ok(version)
             }
         }
     }
 }
 
 impl HttpArielMiddleware {
-    #[async_recursion::async_recursion]
-    async fn get(&mut self, url: String) -> anyhow::Result<(String, String)> {
-        let res = self.client.get(url.clone()).send().await?;
-        let status = res.status().clone();
+    fn load_session(path: &Path) -> Option<cookie_store::CookieStore> {
+        let file = std::fs::File::open(path).ok()?;
+        match cookie_store::CookieStore::load_json(std::io::BufReader::new(file)) {
+            Ok(cookies) => {
+                log::debug!("restored session from {:?}", path);
+                Some(cookies)
+            }
+            Err(e) => {
+                log::warn!("could not restore session from {:?}: {}", path, e);
+                None
+            }
+        }
+    }
 
-        if !status.is_success() {
-            anyhow::bail!("posting to url '{}', status {}", url, status)
+    /// The names of the cookies held, without their values, for logging.
+    fn redacted_cookies(&self) -> String {
+        let cookies = self.cookies.lock().unwrap();
+        let names: Vec<_> = cookies
+            .iter_unexpired()
+            .map(|c| format!("{}=***", c.name()))
+            .collect();
+        names.join("; ")
+    }
+
+    /// Save the cookies, including those that only last for the session, to
+    /// the file in the configuration, readable only by the user.
+    fn save_session(&self) -> crate::Result<()> {
+        let path = match &self.config.session_path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(path)?;
+        #[cfg(unix)]
+        std::fs::set_permissions(path, std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+
+        let cookies = self.cookies.lock().unwrap();
+        for cookie in cookies.iter_unexpired() {
+            let line = serde_json::to_string(cookie).map_err(std::io::Error::from)?;
+            std::io::Write::write_all(&mut file, format!("{}\n", line).as_bytes())?;
+        }
+        log::debug!("saved session to {:?}", path);
+        Ok(())
+    }
+
+    /// Wait for `fut`, giving up if the server sends nothing for longer than
+    /// the read timeout.
+    async fn read<T>(
+        &self,
+        url: &str,
+        fut: impl std::future::Future<Output = reqwest::Result<T>>,
+    ) -> crate::Result<T> {
+        match self.config.network.read_timeout() {
+            Some(timeout) => tokio::time::timeout(timeout, fut)
+                .await
+                .map_err(|_| Error::Timeout {
+                    url: url.to_string(),
+                    secs: timeout.as_secs(),
+                })?
+                .map_err(Error::from),
+            None => Ok(fut.await?),
+        }
+    }
+
+    /// Send the request built by `request`, trying it again as the retry
+    /// policy says if the connection fails or the server answers with a
+    /// retryable status. Any other unsuccessful status is an error. The
+    /// response is counted among the requests in flight until the returned
+    /// turn is dropped.
+    async fn send(
+        &self,
+        url: &str,
+        request: impl Fn() -> reqwest::RequestBuilder,
+    ) -> crate::Result<(reqwest::Response, RequestTurn<'_>)> {
+        let policy = &self.config.network.retry;
+        let mut attempt = 1;
+        loop {
+            let turn = self.limiter.request().await;
+            let (error, retry_after) = match self.read(url, request().send()).await {
+                Ok(res) if res.status().is_success() => return Ok((res, turn)),
+                Ok(res) => {
+                    let status = res.status();
+                    let error = Error::Http {
+                        status,
+                        url: url.to_string(),
+                    };
+                    if !policy.retries_status(status.as_u16()) {
+                        return Err(error);
+                    }
+                    (error, Self::retry_after(&res))
+                }
+                Err(e) if e.is_transient() => (e, None),
+                Err(e) => return Err(e),
+            };
+            // Waiting to retry leaves room for the other requests.
+            drop(turn);
+
+            let delay = match retry_after {
+                Some(delay) if delay > policy.max_backoff() => {
+                    log::warn!("{}, and asked to wait {:?} to retry", error, delay);
+                    return Err(error);
+                }
+                Some(delay) => delay,
+                None => policy.backoff(attempt),
+            };
+            if attempt >= policy.max_attempts {
+                return Err(error);
+            }
+            self.wait_to_retry(&error, attempt, delay).await;
+            attempt += 1;
         }
+    }
+
+    /// How long the server asked to wait with `Retry-After`, in seconds or
+    /// until a date.
+    fn retry_after(res: &reqwest::Response) -> Option<std::time::Duration> {
+        let value = res.headers().get(reqwest::header::RETRY_AFTER)?;
+        let value = value.to_str().ok()?.trim();
+        match value.parse::<u64>() {
+            Ok(secs) => Some(std::time::Duration::from_secs(secs)),
+            Err(_) => {
+                let date = httpdate::parse_http_date(value).ok()?;
+                Some(
+                    date.duration_since(std::time::SystemTime::now())
+                        .unwrap_or_default(),
+                )
+            }
+        }
+    }
+
+    async fn wait_to_retry(&self, error: &Error, attempt: u32, delay: std::time::Duration) {
+        log::warn!(
+            "{}, trying again in {:.1}s (attempt {} of {})",
+            error,
+            delay.as_secs_f64(),
+            attempt + 1,
+            self.config.network.retry.max_attempts
+        );
+        self.retries
+            .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        tokio::time::sleep(delay).await;
+    }
+
+    /// GET a whole segment of a stream, starting it over if the connection
+    /// drops while reading it.
+    async fn get_segment(
+        &self,
+        url: &str,
+        chunk_done_size_chan: &tokio::sync::mpsc::UnboundedSender<u64>,
+    ) -> crate::Result<Vec<u8>> {
+        let policy = &self.config.network.retry;
+        let mut attempt = 1;
+        let mut reported = 0;
+        loop {
+            let (res, first, _, turn) = self.get_body_from(url.to_string(), 0, None).await?;
+            let mut segment = vec![];
+            let written = self
+                .write_body(
+                    res,
+                    first,
+                    &mut segment,
+                    0,
+                    &mut reported,
+                    chunk_done_size_chan,
+                )
+                .await;
+            drop(turn);
+            match written {
+                Ok(()) => return Ok(segment),
+                Err(e) if e.is_transient() && attempt < policy.max_attempts => {
+                    self.wait_to_retry(&e, attempt, policy.backoff(attempt))
+                        .await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    #[async_recursion::async_recursion]
+    async fn get(&self, url: String) -> crate::Result<(String, String)> {
+        let (res, turn) = self.send(&url, || self.client.get(url.clone())).await?;
 
         let url = res.url().clone();
-        let text = res.text().await?;
+        let status = res.status();
+        let text = self.read(url.as_str(), res.text()).await?;
+        drop(turn);
 
         if text.contains(r#"<META HTTP-EQUIV="REFRESH" CONTENT="0; URL=v5">"#) {
             let url = url.join("v5")?.to_string();
             return self.get(url).await;
         }
 
-        log::debug!("{} --- {}", url, text);
+        log::debug!("GET {} -> {} ({} bytes)", url, status, text.len());
         Ok((url.to_string(), text))
     }
 
     #[async_recursion::async_recursion]
     async fn post(
-        &mut self,
+        &self,
         url: String,
         form: Vec<(String, String)>,
-    ) -> anyhow::Result<(String, String)> {
-        log::info!("{:?}", self.cookies);
-
-        let req = self.client.post(url.clone()).form(&form);
-        log::info!("{:?}", req);
-        let res = req.send().await?;
-        let status = res.status().clone();
+    ) -> crate::Result<(String, String)> {
+        log::debug!(
+            "POST {} with {} and cookies {}",
+            url,
+            crate::password::redact_form(&form),
+            self.redacted_cookies()
+        );
 
-        if !status.is_success() {
-            anyhow::bail!("posting to url '{}', status {}", url, status)
-        }
+        let (res, turn) = self
+            .send(&url, || self.client.post(url.clone()).form(&form))
+            .await?;
 
         let url = res.url().clone();
-        let text = res.text().await?;
+        let status = res.status();
+        let text = self.read(url.as_str(), res.text()).await?;
+        drop(turn);
 
         if text.contains(r#"<META HTTP-EQUIV="REFRESH" CONTENT="0; URL=v5">"#) {
             let url = url.join("v5")?.to_string();
             return self.post(url, form).await;
         }
 
-        log::debug!("{} --- {}", url, text);
+        log::debug!("POST {} -> {} ({} bytes)", url, status, text.len());
         Ok((url.to_string(), text))
     }
 
-    pub(crate) async fn get_bytes(&mut self, url: String) -> anyhow::Result<bytes::Bytes> {
-        let res = self.client.get(url.clone()).send().await?;
-        let status = res.status().clone();
-
-        if !status.is_success() {
-            anyhow::bail!("posting to url '{}', status {}", url, status)
+    pub(crate) async fn get_bytes(&self, url: String) -> crate::Result<bytes::Bytes> {
+        let mut logged_in_again = false;
+        loop {
+            let (res, turn) = self.send(&url, || self.client.get(url.clone())).await?;
+            let bytes = self.read(&url, res.bytes()).await?;
+            drop(turn);
+            if !ArielLoginPage::is_login_page_raw(&bytes) {
+                return Ok(bytes);
+            }
+            self.log_in_again(&mut logged_in_again).await?;
         }
+    }
 
-        Ok(res.bytes().await?)
+    /// Log in again after Ariel answered with its login page instead of what
+    /// was asked, unless that already happened once for the same request.
+    async fn log_in_again(&self, logged_in_again: &mut bool) -> crate::Result<()> {
+        if std::mem::replace(logged_in_again, true) {
+            return Err(Error::SessionExpired);
+        }
+        log::info!("got the login page, logging in again");
+        self.login().await
     }
 
-    async fn get_size_generic(&mut self, url: reqwest::Url) -> anyhow::Result<u64> {
-        let res = self.client.head(url.clone()).send().await?;
-        let status = res.status().clone();
+    /// GET `url` asking for the bytes starting at `offset`, as long as the
+    /// file still matches the `if_range` ETag or date, logging in again if
+    /// Ariel answers with its login page. Returns the response together with
+    /// its first chunk (the whole body for HTML pages, to look for the login
+    /// form in), whether the server answered with a partial content and the
+    /// turn of the request.
+    async fn get_body_from(
+        &self,
+        url: String,
+        offset: u64,
+        if_range: Option<&str>,
+    ) -> crate::Result<(reqwest::Response, bytes::Bytes, bool, RequestTurn<'_>)> {
+        let mut logged_in_again = false;
+        loop {
+            let (mut res, turn) = self
+                .send(&url, || {
+                    let mut req = self.client.get(url.clone());
+                    if offset > 0 {
+                        req = req.header(reqwest::header::RANGE, format!("bytes={}-", offset));
+                        if let Some(validator) = if_range {
+                            req = req.header(reqwest::header::IF_RANGE, validator);
+                        }
+                    }
+                    req
+                })
+                .await?;
+            let status = res.status();
+            let is_html = res
+                .headers()
+                .get(reqwest::header::CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .is_some_and(|v| v.starts_with("text/html"));
+
+            let first = if is_html {
+                let mut body = bytes::BytesMut::new();
+                while let Some(chunk) = self.read(&url, res.chunk()).await? {
+                    body.extend_from_slice(&chunk);
+                }
+                body.freeze()
+            } else {
+                self.read(&url, res.chunk()).await?.unwrap_or_default()
+            };
+            if is_html && ArielLoginPage::is_login_page_raw(&first) {
+                drop(turn);
+                self.log_in_again(&mut logged_in_again).await?;
+                continue;
+            }
 
-        if !status.is_success() {
-            anyhow::bail!("HEAD to url '{}', status {}", url, status)
+            let partial = status == reqwest::StatusCode::PARTIAL_CONTENT;
+            return Ok((res, first, partial, turn));
         }
+    }
 
-        if let Some(size) = res.headers().get("Content-Length") {
-            if let Ok(size) = size.to_str()?.parse::<u64>() {
-                return Ok(size);
+    /// Write `first` and the rest of the body of `res`, which starts at byte
+    /// `start` of the file, into `writer` one chunk at a time, keeping within
+    /// the bandwidth cap. Only the bytes past the `reported` ones are reported
+    /// on `chunk_done_size_chan`, so that starting a body over after an error
+    /// does not count its bytes twice.
+    async fn write_body(
+        &self,
+        mut res: reqwest::Response,
+        first: bytes::Bytes,
+        writer: &mut (impl tokio::io::AsyncWrite + Unpin),
+        start: u64,
+        reported: &mut u64,
+        chunk_done_size_chan: &tokio::sync::mpsc::UnboundedSender<u64>,
+    ) -> crate::Result<()> {
+        let url = res.url().to_string();
+        let mut position = start;
+        let mut chunk = Some(first);
+        while let Some(bytes) = chunk {
+            writer.write_all(&bytes).await?;
+            let end = position + bytes.len() as u64;
+            if end > *reported {
+                chunk_done_size_chan.send(end - (*reported).max(position))?;
+                *reported = end;
             }
+            position = end;
+            self.limiter.downloaded(bytes.len() as u64).await;
+            chunk = self.read(&url, res.chunk()).await?;
         }
+        writer.flush().await?;
+        Ok(())
+    }
+
+    async fn get_size_generic(&self, url: reqwest::Url) -> crate::Result<u64> {
+        let (version, _) = self.head_generic(url).await?;
+        Ok(version.size)
+    }
+
+    /// HEAD `url`, returning what the server tells about it and whether it
+    /// accepts range requests for it.
+    async fn head_generic(&self, url: reqwest::Url) -> crate::Result<(ArielRemoteVersion, bool)> {
+        let (res, _turn) = self
+            .send(url.as_str(), || self.client.head(url.clone()))
+            .await?;
 
-        log::warn!("Could not find Content-Length!");
-        Ok(0)
+        let accepts_ranges = res
+            .headers()
+            .get(reqwest::header::ACCEPT_RANGES)
+            .map(|v| v.as_bytes() == b"bytes")
+            .unwrap_or(false);
+        let header = |name| {
+            res.headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+        };
+
+        let size = match header(reqwest::header::CONTENT_LENGTH).map(|s| s.parse::<u64>()) {
+            Some(Ok(size)) => size,
+            _ => {
+                log::warn!("Could not find Content-Length!");
+                0
+            }
+        };
+        let version = ArielRemoteVersion {
+            size,
+            etag: header(reqwest::header::ETAG),
+            last_modified: header(reqwest::header::LAST_MODIFIED),
+        };
+        Ok((version, accepts_ranges))
     }
 
-    async fn get_size_of_stream(&mut self, _: reqwest::Url) -> anyhow::Result<u64> {
-        //    All this is way too slow. One day maybe...
-        //    let chunks = self.get_m3u8_segments(url.clone()).await?;
-        //    let mut bytes = 0;
-        //    for chunk in chunks {
-        //        println!("getting size of chunk");
-        //        bytes += self
-        //            .get_size_generic(chunk.uri.parse::<url::Url>().unwrap())
-        //            .await?;
-        //    }
-        //    Ok(bytes)
-        Ok(0)
+    async fn get_size_of_stream(&self, url: reqwest::Url) -> crate::Result<u64> {
+        let stream = self.get_m3u8_stream(url).await?;
+        if !self.config.exact_stream_size {
+            return Ok(stream.estimate_size());
+        }
+
+        let sizes = futures::stream::iter(stream.segments)
+            .map(|segment| async move {
+                let url = segment.uri.parse::<reqwest::Url>()?;
+                self.get_size_generic(url).await
+            })
+            .buffer_unordered(8)
+            .collect::<Vec<_>>()
+            .await;
+
+        let mut bytes = 0;
+        for size in sizes {
+            bytes += size?;
+        }
+        Ok(bytes)
     }
 
     async fn download_stream(
-        &mut self,
+        &self,
         path: String,
         data: ArielPageData,
-        chunk_done_size_chan: std::sync::mpsc::Sender<u64>,
-    ) -> anyhow::Result<()> {
-        let chunks = self.get_m3u8_segments(data.url.clone()).await?;
+        chunk_done_size_chan: tokio::sync::mpsc::UnboundedSender<u64>,
+    ) -> crate::Result<PathBuf> {
+        let chunks = self.get_m3u8_stream(data.url.clone()).await?.segments;
 
-        let mut path = PathBuf::from(path);
-        path.push(heck::AsSnakeCase(data.from_site.clone()).to_string());
-        path.push(heck::AsSnakeCase(data.from_ambient.clone()).to_string());
-        path.push(heck::AsSnakeCase(data.from_thread.clone()).to_string());
-        path.push(data.get_name());
+        let path = data.get_path(&path);
+        let segments_path = match self.config.remux {
+            Some(_) => remux::segments_path(&path),
+            None => path.clone(),
+        };
 
         if !path.exists() {
             log::trace!("creating path {:?}", path);
-            std::fs::create_dir_all(path.parent().unwrap())?
+            tokio::fs::create_dir_all(path.parent().unwrap()).await?
         }
 
-        let mut file = std::fs::OpenOptions::new()
+        let mut file = tokio::fs::OpenOptions::new()
             .write(true)
             .create(true)
             .truncate(true)
-            .open(path)?;
+            .open(&segments_path)
+            .await?;
 
-        let mut bytes;
+        let mut keys = std::collections::HashMap::new();
         for chunk in chunks {
-            bytes = self.get_bytes(chunk.uri).await?;
-
-            while ArielLoginPage::is_login_page_raw(&bytes) {
-                self.login().await?;
-                bytes = self.get_bytes(data.url.to_string()).await?;
+            let segment = self.get_segment(&chunk.uri, &chunk_done_size_chan).await?;
+
+            match chunk.key {
+                Some(Key {
+                    method: KeyMethod::AES128,
+                    uri: Some(key_uri),
+                    iv: Some(iv),
+                    ..
+                }) => {
+                    if !keys.contains_key(&key_uri) {
+                        log::info!("fetching key {}", key_uri);
+                        let key = self.get_bytes(key_uri.clone()).await?;
+                        keys.insert(key_uri.clone(), key);
+                    }
+
+                    let decrypted =
+                        m3u8::decrypt_segment(&chunk.uri, &keys[&key_uri], &iv, &segment)?;
+                    file.write_all(&decrypted).await?;
+                }
+                Some(Key {
+                    method: KeyMethod::AES128,
+                    uri: None,
+                    ..
+                }) => {
+                    return Err(Error::Stream {
+                        url: chunk.uri,
+                        reason: "missing key URI".to_string(),
+                    })
+                }
+                Some(key) => {
+                    return Err(Error::Stream {
+                        url: chunk.uri,
+                        reason: format!("unsupported encryption {:?}", key.method),
+                    })
+                }
+                None => file.write_all(&segment).await?,
             }
-
-            let len = bytes.len();
-
-            std::io::Write::write_all(&mut file, &bytes)?;
-            chunk_done_size_chan.send(len.try_into().unwrap())?;
         }
+        file.flush().await?;
+        drop(file);
         drop(chunk_done_size_chan);
-        return Ok(());
+
+        match self.config.remux {
+            Some(container) => {
+                let output = remux::output_path(&path, container);
+                log::info!("remuxing {:?} into {:?}", segments_path, output);
+                // Remuxing a whole lesson takes a while: keep it off the
+                // runtime, so that the other downloads go on meanwhile.
+                let (input, remuxed) = (segments_path.clone(), output.clone());
+                tokio::task::spawn_blocking(move || remux::remux(&input, &remuxed))
+                    .await
+                    .map_err(|e| Error::Remux {
+                        path: segments_path.clone(),
+                        reason: e.to_string(),
+                    })??;
+                tokio::fs::remove_file(segments_path).await?;
+                Ok(output)
+            }
+            None => Ok(path),
+        }
     }
 
     async fn download_generic(
-        &mut self,
+        &self,
         path: String,
         data: ArielPageData,
-        chunk_done_size_chan: std::sync::mpsc::Sender<u64>,
-    ) -> anyhow::Result<()> {
-        let mut path_buf = PathBuf::from(path.clone());
-        path_buf.push(heck::AsSnakeCase(data.from_site.clone()).to_string());
-        path_buf.push(heck::AsSnakeCase(data.from_ambient.clone()).to_string());
-        path_buf.push(heck::AsSnakeCase(data.from_thread.clone()).to_string());
-        path_buf.push(data.get_name());
+        chunk_done_size_chan: tokio::sync::mpsc::UnboundedSender<u64>,
+    ) -> crate::Result<PathBuf> {
+        let path_buf = data.get_path(&path);
+        let mut part_path = path_buf.clone().into_os_string();
+        part_path.push(".part");
+        let part_path = PathBuf::from(part_path);
+        let mut validator_path = part_path.clone().into_os_string();
+        validator_path.push(".validator");
+        let validator_path = PathBuf::from(validator_path);
         log::info!(
             "trying to download {} into {:?}",
             data.url,
             path_buf.to_str()
         );
 
-        let mut bytes = self.get_bytes(data.url.to_string()).await?;
-
-        while ArielLoginPage::is_login_page_raw(&bytes) {
-            self.login().await?;
-            bytes = self.get_bytes(data.url.to_string()).await?;
+        if !path_buf.exists() {
+            log::trace!("creating path {:?}", path_buf);
+            tokio::fs::create_dir_all(path_buf.parent().unwrap()).await?
         }
 
-        let len = bytes.len();
+        // Dropped connections are resumed from what was already written, as
+        // long as the server allows it and the file did not change since: the
+        // ETag (or the date) of the file is kept next to the partial download
+        // and sent back with `If-Range`.
+        let policy = &self.config.network.retry;
+        let mut attempt = 1;
+        let mut reported = 0;
+        loop {
+            let (version, accepts_ranges) = self.head_generic(data.url.clone()).await?;
+            let size = version.size;
+            let validator = version.etag.or(version.last_modified);
+            let unchanged = tokio::fs::read_to_string(&validator_path).await.ok() == validator;
+            let offset = match tokio::fs::metadata(&part_path).await {
+                Ok(meta) if accepts_ranges && unchanged => meta.len(),
+                Ok(_) => {
+                    log::info!("{:?} cannot be resumed, starting over", part_path);
+                    0
+                }
+                Err(_) => 0,
+            };
+            let offset = if size != 0 && offset > size {
+                log::info!(
+                    "{:?} is larger than the remote file, starting over",
+                    part_path
+                );
+                0
+            } else {
+                offset
+            };
+
+            if size != 0 && offset == size {
+                log::info!("{:?} is already complete", part_path);
+                if offset > reported {
+                    chunk_done_size_chan.send(offset - reported)?;
+                }
+                break;
+            }
+
+            if offset == 0 {
+                match &validator {
+                    Some(validator) => tokio::fs::write(&validator_path, validator).await?,
+                    None if validator_path.exists() => {
+                        tokio::fs::remove_file(&validator_path).await?
+                    }
+                    None => {}
+                }
+            }
 
-        chunk_done_size_chan.send(len.try_into().unwrap())?;
+            let (res, first, partial, turn) = self
+                .get_body_from(data.url.to_string(), offset, validator.as_deref())
+                .await?;
 
-        if !path_buf.exists() {
-            log::trace!("creating path {:?}", path_buf);
-            std::fs::create_dir_all(path_buf.parent().unwrap())?
-        }
+            if partial {
+                log::info!("resuming {:?} from byte {}", part_path, offset);
+                if offset > reported {
+                    chunk_done_size_chan.send(offset - reported)?;
+                    reported = offset;
+                }
+            }
 
-        let mut file = std::fs::OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(path_buf)?;
+            let mut file = tokio::fs::OpenOptions::new()
+                .write(true)
+                .create(true)
+                .append(partial)
+                .truncate(!partial)
+                .open(&part_path)
+                .await?;
+
+            let written = self
+                .write_body(
+                    res,
+                    first,
+                    &mut file,
+                    if partial { offset } else { 0 },
+                    &mut reported,
+                    &chunk_done_size_chan,
+                )
+                .await;
+            drop(turn);
+            match written {
+                Ok(()) => break,
+                Err(e) if e.is_transient() && attempt < policy.max_attempts => {
+                    self.wait_to_retry(&e, attempt, policy.backoff(attempt))
+                        .await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
 
-        std::io::Write::write_all(&mut file, &bytes)?;
+        tokio::fs::rename(part_path, &path_buf).await?;
+        if validator_path.exists() {
+            tokio::fs::remove_file(validator_path).await?;
+        }
 
         drop(chunk_done_size_chan);
-        Ok(())
+        Ok(path_buf)
     }
 }
@@ -1,34 +1,187 @@
-use m3u8_rs::Playlist;
+use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, KeyIvInit};
+use m3u8_rs::{Key, KeyMethod, MediaPlaylist, MediaSegment, Playlist, VariantStream};
 use url::Url;
 
 use super::http::HttpArielMiddleware;
+use crate::{ariel::ArielStreamQuality, Error};
+
+/// The segments of a media playlist, together with the bandwidth advertised
+/// by the master playlist for it, if any.
+#[derive(Debug, Default)]
+pub(super) struct HlsStream {
+    pub segments: Vec<MediaSegment>,
+    pub bandwidth: Option<u64>,
+}
+
+impl HlsStream {
+    /// Make the segments of `pl` absolute with respect to `uri` and give each
+    /// of them the key it is encrypted with, if any.
+    pub fn from_media_playlist(uri: &Url, pl: MediaPlaylist) -> crate::Result<HlsStream> {
+        let join = |relative: &str| {
+            uri.join(relative)
+                .map(|u| u.to_string())
+                .map_err(|e| Error::Stream {
+                    url: uri.to_string(),
+                    reason: format!("invalid URI '{}': {}", relative, e),
+                })
+        };
+
+        let mut segs = pl.segments;
+        let mut key: Option<Key> = None;
+        for (i, seg) in segs.iter_mut().enumerate() {
+            seg.uri = join(&seg.uri)?;
+
+            // An EXT-X-KEY applies to every following segment, until the next one.
+            if let Some(seg_key) = seg.key.take() {
+                key = match seg_key.method {
+                    KeyMethod::None => None,
+                    _ => Some(seg_key),
+                };
+            }
+            // m3u8-rs rejects `METHOD=NONE` without an IV, leaving it as an unknown tag.
+            if seg.unknown_tags.iter().any(|t| {
+                t.tag == "X-KEY" && t.rest.as_deref().is_some_and(|r| r.contains("METHOD=NONE"))
+            }) {
+                key = None;
+            }
+            seg.key = match key.clone() {
+                Some(mut key) => {
+                    if let Some(key_uri) = &key.uri {
+                        key.uri = Some(join(key_uri)?);
+                    }
+                    if key.iv.is_none() {
+                        let sequence = pl.media_sequence + i as u64;
+                        key.iv = Some(format!("0x{:032x}", sequence));
+                    }
+                    Some(key)
+                }
+                None => None,
+            };
+        }
+        Ok(HlsStream {
+            segments: segs,
+            bandwidth: None,
+        })
+    }
+
+    /// Guess the size in bytes of the stream from the playlist alone: the sum
+    /// of the byte ranges when every segment has one, otherwise the duration
+    /// times the bandwidth. Returns 0 when neither is available.
+    pub fn estimate_size(&self) -> u64 {
+        let ranges = self
+            .segments
+            .iter()
+            .map(|s| s.byte_range.as_ref().map(|r| r.length))
+            .collect::<Option<Vec<_>>>();
+
+        match (ranges, self.bandwidth) {
+            (Some(ranges), _) if !ranges.is_empty() => ranges.iter().sum(),
+            (_, Some(bandwidth)) => {
+                let duration: f64 = self.segments.iter().map(|s| s.duration as f64).sum();
+                (duration * bandwidth as f64 / 8.0) as u64
+            }
+            _ => 0,
+        }
+    }
+}
+
+/// Pick the variant of a master playlist that best fits `quality`. When no
+/// variant fits the given limit, the smallest one is used.
+fn select_variant(
+    variants: &[VariantStream],
+    quality: ArielStreamQuality,
+) -> Option<&VariantStream> {
+    let variants = variants.iter().filter(|v| !v.is_i_frame);
+    let lowest = variants.clone().min_by_key(|v| v.bandwidth);
+
+    match quality {
+        ArielStreamQuality::Highest => best_variant(variants),
+        ArielStreamQuality::Lowest => lowest,
+        ArielStreamQuality::MaxHeight(height) => {
+            best_variant(variants.filter(|v| v.resolution.is_some_and(|r| r.height <= height)))
+                .or(lowest)
+        }
+        ArielStreamQuality::MaxBandwidth(bandwidth) => {
+            best_variant(variants.filter(|v| v.bandwidth <= bandwidth)).or(lowest)
+        }
+    }
+}
+
+/// Decrypt a segment encrypted with AES-128-CBC and PKCS7 padding.
+pub(super) fn decrypt_segment(
+    uri: &str,
+    key: &[u8],
+    iv: &str,
+    data: &[u8],
+) -> crate::Result<Vec<u8>> {
+    let error = |reason: String| Error::Stream {
+        url: uri.to_string(),
+        reason,
+    };
+    if key.len() != 16 {
+        return Err(error(format!(
+            "AES-128 key must be 16 bytes long, got {}",
+            key.len()
+        )));
+    }
+    let iv = parse_iv(iv).ok_or_else(|| error(format!("invalid IV '{}'", iv)))?;
+
+    cbc::Decryptor::<aes::Aes128>::new(key.into(), &iv.into())
+        .decrypt_padded_vec_mut::<Pkcs7>(data)
+        .map_err(|_| error("could not decrypt segment: bad padding".to_string()))
+}
+
+/// Parse an `IV` attribute of EXT-X-KEY, a 128 bit hexadecimal number.
+fn parse_iv(iv: &str) -> Option<[u8; 16]> {
+    let hex = iv
+        .strip_prefix("0x")
+        .or_else(|| iv.strip_prefix("0X"))
+        .unwrap_or(iv);
+    match u128::from_str_radix(hex, 16) {
+        Ok(iv) if hex.len() <= 32 => Some(iv.to_be_bytes()),
+        _ => None,
+    }
+}
+
+fn best_variant<'a>(
+    variants: impl Iterator<Item = &'a VariantStream>,
+) -> Option<&'a VariantStream> {
+    variants.max_by_key(|v| (v.bandwidth, v.resolution.map(|r| r.height)))
+}
 
 impl HttpArielMiddleware {
     #[async_recursion::async_recursion]
-    pub(super) async fn get_m3u8_segments(
-        &mut self,
-        uri: Url,
-    ) -> anyhow::Result<Vec<m3u8_rs::MediaSegment>> {
+    pub(super) async fn get_m3u8_stream(&self, uri: Url) -> crate::Result<HlsStream> {
         let bytes = self.get_bytes(uri.to_string()).await?;
         let parsed = m3u8_rs::parse_playlist_res(&bytes);
 
         match parsed {
             Ok(Playlist::MasterPlaylist(pl)) => {
-                if pl.variants.len() == 0 {
-                    return Ok(vec![]);
-                } else {
-                    let variant = &pl.variants[0];
-                    return self.get_m3u8_segments(uri.join(&variant.uri)?).await;
-                }
-            }
-            Ok(Playlist::MediaPlaylist(pl)) => {
-                let mut segs = pl.segments;
-                for seg in &mut segs {
-                    seg.uri = uri.join(&seg.uri).unwrap().to_string();
+                match select_variant(&pl.variants, self.config.stream_quality) {
+                    Some(variant) => {
+                        log::info!(
+                            "picked variant {} ({} bps, {:?})",
+                            variant.uri,
+                            variant.bandwidth,
+                            variant.resolution
+                        );
+                        let variant_uri = uri.join(&variant.uri).map_err(|e| Error::Stream {
+                            url: uri.to_string(),
+                            reason: format!("invalid URI '{}': {}", variant.uri, e),
+                        })?;
+                        let mut stream = self.get_m3u8_stream(variant_uri).await?;
+                        stream.bandwidth =
+                            Some(variant.average_bandwidth.unwrap_or(variant.bandwidth));
+                        Ok(stream)
+                    }
+                    None => Ok(HlsStream::default()),
                 }
-                Ok(segs)
             }
-            Err(e) => anyhow::bail!("{}", e),
+            Ok(Playlist::MediaPlaylist(pl)) => HlsStream::from_media_playlist(&uri, pl),
+            Err(e) => Err(Error::Parse {
+                page: uri.to_string(),
+                reason: e.to_string(),
+            }),
         }
     }
 }
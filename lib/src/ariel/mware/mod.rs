@@ -1,34 +1,47 @@
+use std::path::PathBuf;
+
 use async_trait::async_trait;
 
 use super::{
+    manifest::ArielRemoteVersion,
+    map::ArielSitemap,
     page::{ArielPageData, ArielTitlePage},
     ArielUserConfig,
 };
 
+pub mod fixture;
 pub mod http;
 mod m3u8;
+mod remux;
 #[async_trait]
 pub trait ArielMiddleware: Sync + Send + std::fmt::Debug {
-    fn new(config: ArielUserConfig) -> Self
+    fn new(config: ArielUserConfig) -> crate::Result<Self>
     where
         Self: Sized;
-    async fn login(&mut self) -> anyhow::Result<()>;
-    async fn search(&mut self, course_name: &str) -> anyhow::Result<Vec<ArielTitlePage>>;
-    async fn get(&mut self, url: String) -> anyhow::Result<(String, String)>;
+    /// Where the middleware looks for Ariel.
+    fn sitemap(&self) -> &ArielSitemap;
+    /// How many requests were tried again after failing.
+    fn retries(&self) -> u64 {
+        0
+    }
+    async fn login(&self) -> crate::Result<()>;
+    async fn search(&self, course_name: &str) -> crate::Result<Vec<ArielTitlePage>>;
+    async fn get(&self, url: String) -> crate::Result<(String, String)>;
     async fn post(
-        &mut self,
+        &self,
         url: String,
         form: Vec<(String, String)>,
-    ) -> anyhow::Result<(String, String)>;
+    ) -> crate::Result<(String, String)>;
 
-    async fn is_logged_in(&mut self) -> anyhow::Result<()>;
+    async fn is_logged_in(&self) -> crate::Result<()>;
 
     async fn download<'a>(
-        &mut self,
+        &self,
         path: String,
         data: ArielPageData,
-        chunk_done_size_chan: std::sync::mpsc::Sender<u64>,
-    ) -> anyhow::Result<()>;
+        chunk_done_size_chan: tokio::sync::mpsc::UnboundedSender<u64>,
+    ) -> crate::Result<PathBuf>;
 
-    async fn get_size<'a>(&mut self, data: &'a ArielPageData) -> anyhow::Result<u64>;
+    /// Ask the server about `data` without downloading it.
+    async fn get_version<'a>(&self, data: &'a ArielPageData) -> crate::Result<ArielRemoteVersion>;
 }
@@ -0,0 +1,95 @@
+use std::path::{Path, PathBuf};
+
+use ffmpeg_next::{codec, encoder, format, media, Rational};
+
+use crate::{ariel::ArielStreamContainer, Error};
+
+const VIDEO_EXTENSIONS: [&str; 4] = ["mp4", "m4v", "mkv", "ts"];
+
+/// Where the raw MPEG-TS segments of the stream to be saved in `path` are
+/// collected before remuxing.
+pub(super) fn segments_path(path: &Path) -> PathBuf {
+    let mut segments = path.as_os_str().to_owned();
+    segments.push(".ts.part");
+    PathBuf::from(segments)
+}
+
+/// The path of the remuxed stream, with the extension of `container`.
+pub(super) fn output_path(path: &Path, container: ArielStreamContainer) -> PathBuf {
+    let has_video_ext = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| VIDEO_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+        .unwrap_or(false);
+
+    if has_video_ext {
+        path.with_extension(container.extension())
+    } else {
+        let mut output = path.as_os_str().to_owned();
+        output.push(".");
+        output.push(container.extension());
+        PathBuf::from(output)
+    }
+}
+
+/// Copy the audio, video and subtitle streams of `input` into a new container
+/// at `output` (guessed from its extension), without re-encoding them.
+pub(super) fn remux(input: &Path, output: &Path) -> crate::Result<()> {
+    copy_streams(input, output).map_err(|e| Error::Remux {
+        path: input.to_path_buf(),
+        reason: e.to_string(),
+    })
+}
+
+fn copy_streams(input: &Path, output: &Path) -> Result<(), ffmpeg_next::Error> {
+    ffmpeg_next::init()?;
+    ffmpeg_next::log::set_level(ffmpeg_next::log::Level::Error);
+
+    let mut ictx = format::input(&input)?;
+    let mut octx = format::output(&output)?;
+
+    let mut stream_mapping = vec![-1; ictx.nb_streams() as _];
+    let mut ist_time_bases = vec![Rational(0, 1); ictx.nb_streams() as _];
+    let mut ost_index = 0;
+    for (ist_index, ist) in ictx.streams().enumerate() {
+        let ist_medium = ist.parameters().medium();
+        if ist_medium != media::Type::Audio
+            && ist_medium != media::Type::Video
+            && ist_medium != media::Type::Subtitle
+        {
+            continue;
+        }
+        stream_mapping[ist_index] = ost_index;
+        ist_time_bases[ist_index] = ist.time_base();
+        ost_index += 1;
+
+        let mut ost = octx.add_stream(encoder::find(codec::Id::None))?;
+        ost.set_parameters(ist.parameters());
+        // The codec tag of MPEG-TS streams is not valid in other containers:
+        // let the muxer pick the right one. ffmpeg-next has no setter for it.
+        // SAFETY: the parameters belong to `ost`, which is alive and not
+        // borrowed elsewhere, and `codec_tag` is a plain integer field.
+        unsafe {
+            (*ost.parameters().as_mut_ptr()).codec_tag = 0;
+        }
+    }
+
+    octx.set_metadata(ictx.metadata().to_owned());
+    octx.write_header()?;
+
+    for (stream, mut packet) in ictx.packets() {
+        let ist_index = stream.index();
+        let ost_index = stream_mapping[ist_index];
+        if ost_index < 0 {
+            continue;
+        }
+        let ost = octx.stream(ost_index as _).unwrap();
+        packet.rescale_ts(ist_time_bases[ist_index], ost.time_base());
+        packet.set_position(-1);
+        packet.set_stream(ost_index as _);
+        packet.write_interleaved(&mut octx)?;
+    }
+
+    octx.write_trailer()?;
+    Ok(())
+}
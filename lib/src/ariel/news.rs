@@ -0,0 +1,70 @@
+use url::Url;
+
+/// An announcement ("avviso") published in a site.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ArielAnnouncement {
+    pub from_site: String,
+    pub url: Url,
+    pub title: String,
+    pub author: Option<String>,
+    /// The date as shown by Ariel.
+    pub date: Option<String>,
+    /// The date as `YYYY-MM-DDTHH:MM`, if it could be parsed.
+    pub published: Option<String>,
+    /// The body of the announcement, as HTML.
+    pub body: String,
+}
+
+impl ArielAnnouncement {
+    pub fn to_markdown(&self) -> String {
+        let body = html2md::parse_html(&self.body);
+        body.trim().to_string()
+    }
+
+    /// Whether the announcement was published on `since` (as returned by
+    /// [`parse_date`]) or later. Announcements without a date always are.
+    pub fn is_since(&self, since: &str) -> bool {
+        match &self.published {
+            Some(published) => published.as_str() >= since,
+            None => true,
+        }
+    }
+}
+
+/// Sort `news` newest first, those without a date last.
+pub fn sort_newest_first(news: &mut [ArielAnnouncement]) {
+    news.sort_by(|a, b| match (&a.published, &b.published) {
+        (Some(a), Some(b)) => b.cmp(a),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => std::cmp::Ordering::Equal,
+    });
+}
+
+lazy_static::lazy_static! {
+    static ref DATE_REGEX: regex::Regex = regex::Regex::new(
+        r"^(?:(\d{1,2})/(\d{1,2})/(\d{4})|(\d{4})-(\d{1,2})-(\d{1,2}))(?:[ T](\d{1,2})[:.](\d{2}))?",
+    )
+    .unwrap();
+}
+
+/// Parse a date such as `01/03/2022 12:30` (as shown by Ariel) or
+/// `2022-03-01` into `YYYY-MM-DDTHH:MM`, which sorts chronologically.
+/// Dates that do not exist, such as `31/02/2022`, are rejected.
+pub fn parse_date(date: &str) -> Option<String> {
+    let captures = DATE_REGEX.captures(date.trim())?;
+    let num = |i: usize| -> Option<u32> { captures.get(i)?.as_str().parse().ok() };
+
+    let (year, month, day) = match num(3) {
+        Some(year) => (year, num(2)?, num(1)?),
+        None => (num(4)?, num(5)?, num(6)?),
+    };
+    let (hour, minute) = (num(7).unwrap_or(0), num(8).unwrap_or(0));
+    chrono::NaiveDate::from_ymd_opt(year.try_into().ok()?, month, day)?
+        .and_hms_opt(hour, minute, 0)?;
+
+    Some(format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}",
+        year, month, day, hour, minute
+    ))
+}
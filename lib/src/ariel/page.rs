@@ -1,5 +1,15 @@
+use std::path::PathBuf;
+
 use url::Url;
 
+use super::{
+    forum::{ArielForumPost, ArielForumThread},
+    map::ArielSitemap,
+    news::{self, ArielAnnouncement},
+    tool::{ArielTool, ArielToolKind},
+    tree::ArielAttachment,
+};
+
 pub struct ArielLoginPage {}
 impl ArielLoginPage {
     pub fn is_login_page_raw(raw: &bytes::Bytes) -> bool {
@@ -9,27 +19,29 @@ impl ArielLoginPage {
             m == matcher
         })
     }
-    pub fn is_logged_in(raw: String) -> anyhow::Result<()> {
+    pub fn is_logged_in(raw: String) -> crate::Result<()> {
         let mut options = tl::ParserOptions::new();
         options = options.track_ids();
         options = options.track_classes();
-        let soup = tl::parse(&raw, options).unwrap();
+        let soup = tl::parse(&raw, options).map_err(|e| crate::Error::Parse {
+            page: "login page".to_string(),
+            reason: e.to_string(),
+        })?;
         let parser = soup.parser();
 
-        if let Some(cv_login) = soup.get_element_by_id("cvLogin") {
-            if let Some(_) = cv_login
-                .get(parser)
-                .unwrap()
-                .find_node(parser, &mut |child| {
-                    if let tl::Node::Tag(tag) = child {
-                        if let Some(Some(text_danger)) = tag.attributes().get("class") {
-                            return text_danger == "text-danger";
-                        }
-                    }
-                    return false;
-                })
-            {
-                anyhow::bail!("")
+        let cv_login = soup
+            .get_element_by_id("cvLogin")
+            .and_then(|cv_login| cv_login.get(parser));
+        if let Some(cv_login) = cv_login {
+            let error = cv_login.find_node(parser, &mut |child| match child {
+                tl::Node::Tag(tag) => matches!(
+                    tag.attributes().get("class"),
+                    Some(Some(class)) if class == "text-danger"
+                ),
+                _ => false,
+            });
+            if error.is_some() {
+                return Err(crate::Error::InvalidCredentials);
             }
         }
         Ok(())
@@ -38,91 +50,178 @@ impl ArielLoginPage {
 
 pub struct ArielSearchPage {}
 impl ArielSearchPage {
-    pub fn title_pages(raw: String) -> Vec<ArielTitlePage> {
-        log::debug!("raw: {}", raw);
+    /// The courses listed in the search page at `url`. Courses without a
+    /// valid link are skipped.
+    pub fn title_pages(url: &str, raw: String) -> crate::Result<Vec<ArielTitlePage>> {
         let mut res = vec![];
+        let base = url.parse::<Url>().ok();
 
         let mut options = tl::ParserOptions::new();
         options = options.track_ids();
         options = options.track_classes();
-        let soup = tl::parse(&raw, options).unwrap();
+        let soup = tl::parse(&raw, options).map_err(|e| crate::Error::Parse {
+            page: url.to_string(),
+            reason: e.to_string(),
+        })?;
         let parser = soup.parser();
+        let teacher_url_regex = regex::Regex::new(r".*teacher.*").unwrap();
+        let year_regex = regex::Regex::new(r"\b(\d{4})\s*/\s*(\d{2,4})\b").unwrap();
 
         for handle in soup.get_elements_by_class_name("ariel-project") {
-            let mut url = String::new();
+            let mut href = String::new();
             let mut title = String::new();
-            let teacher_url_regex = regex::Regex::new(r".*teacher.*").unwrap();
             let mut holders = vec![];
             let mut can_access = false;
-            let node = handle.get(parser).unwrap();
+            let mut academic_year = None;
+            let mut degree = None;
+            let mut code = None;
+            let node = match handle.get(parser) {
+                Some(node) => node,
+                None => continue,
+            };
 
             if let Some(children) = node.children() {
                 for child in children.all(parser) {
                     if let tl::Node::Tag(child) = child {
-                        let mut child_class = String::new();
-                        let mut child_href = String::new();
-
-                        if child.attributes().contains("class") {
-                            child_class = child
-                                .attributes()
-                                .get("class")
-                                .unwrap()
-                                .unwrap()
-                                .as_utf8_str()
-                                .to_string();
-                        }
-                        if child.attributes().contains("href") {
-                            child_href = child
-                                .attributes()
-                                .get("href")
-                                .unwrap()
-                                .unwrap()
-                                .as_utf8_str()
-                                .to_string();
-                        }
+                        let attribute = |name| match child.attributes().get(name) {
+                            Some(Some(value)) => value.as_utf8_str().to_string(),
+                            _ => String::new(),
+                        };
+                        let child_class = attribute("class");
+                        let child_href = attribute("href");
+                        let text = child.inner_text(parser).trim().to_string();
+                        let class = child_class.to_lowercase();
+
                         if child_class == "ariel" {
-                            title = child.inner_text(parser).to_string();
-                            url = child_href
+                            title = text;
+                            href = child_href
                         } else if teacher_url_regex.is_match(child_href.as_str()) {
-                            holders.push(child_href);
+                            if let Some(teacher) =
+                                ArielTeacher::from_link(base.as_ref(), &child_href, &text)
+                            {
+                                holders.push(teacher);
+                            }
                         } else if child_class == "bg-tag-success" {
                             can_access = true
+                        } else if class.contains("year") || class.contains("anno") {
+                            academic_year = Some(text);
+                        } else if class.contains("degree") || class.contains("cdl") {
+                            degree = Some(text);
+                        } else if class.contains("code") || class.contains("codice") {
+                            code = Some(text);
                         }
                     }
                 }
             }
+
+            let url = match resolve(base.as_ref(), &href) {
+                Some(url) => url,
+                None => {
+                    log::warn!("skipping course {:?} with bad link {:?}", title, href);
+                    continue;
+                }
+            };
+            if academic_year.is_none() {
+                academic_year = year_regex
+                    .captures(&node.inner_text(parser))
+                    .map(|c| format!("{}/{}", &c[1], &c[2]));
+            }
             res.push(ArielTitlePage {
-                url: url.parse::<url::Url>().unwrap(),
+                url,
                 title,
                 holders,
                 can_access,
+                academic_year,
+                degree,
+                code,
             });
         }
-        res
+        Ok(res)
+    }
+}
+
+/// `href` relative to `base`, if any, or `None` if it is empty or invalid.
+fn resolve(base: Option<&Url>, href: &str) -> Option<Url> {
+    if href.trim().is_empty() {
+        return None;
+    }
+    match base {
+        Some(base) => base.join(href).ok(),
+        None => href.parse::<Url>().ok(),
+    }
+}
+
+/// A teacher holding a course.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ArielTeacher {
+    /// The identifier of the teacher in the URL of their page.
+    pub id: String,
+    pub name: String,
+    pub url: Url,
+}
+
+impl ArielTeacher {
+    /// The teacher linked by `href`, relative to `base` if given.
+    fn from_link(base: Option<&Url>, href: &str, name: &str) -> Option<ArielTeacher> {
+        let url = resolve(base, href)?;
+        let id = url
+            .query_pairs()
+            .find(|(key, _)| key == "id")
+            .map(|(_, id)| id.to_string())
+            .or_else(|| {
+                url.path_segments()?
+                    .rfind(|s| !s.is_empty())
+                    .map(|s| s.to_string())
+            })?;
+        let name = if name.is_empty() {
+            id.clone()
+        } else {
+            name.to_string()
+        };
+        Some(ArielTeacher { id, name, url })
+    }
+
+    /// Whether the name or the identifier of the teacher contain `query`,
+    /// ignoring case.
+    pub fn matches(&self, query: &str) -> bool {
+        let query = query.to_lowercase();
+        self.name.to_lowercase().contains(&query) || self.id.to_lowercase().contains(&query)
+    }
+}
+
+impl std::fmt::Display for ArielTeacher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct ArielTitlePage {
     pub title: String,
     pub url: Url,
-    pub holders: Vec<String>,
+    pub holders: Vec<ArielTeacher>,
     pub can_access: bool,
+    /// The academic year, such as `2021/2022`.
+    pub academic_year: Option<String>,
+    pub degree: Option<String>,
+    pub code: Option<String>,
 }
 
 impl std::fmt::Display for ArielTitlePage {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.title)?;
-        let mut holders = String::from("[");
-        for (i, holder) in self.holders.clone().into_iter().enumerate() {
-            if i < self.holders.len() - 1 {
-                holders.push_str(format!("{}, ", holder).as_str());
-            } else {
-                holders.push_str(holder.as_str());
-            }
+        if let Some(code) = &self.code {
+            write!(f, " ({})", code)?;
         }
-        holders.push_str("]");
-        write!(f, " {}", holders)?;
+        if let Some(year) = &self.academic_year {
+            write!(f, " {}", year)?;
+        }
+        let holders = self
+            .holders
+            .iter()
+            .map(|h| h.name.as_str())
+            .collect::<Vec<_>>();
+        write!(f, " [{}]", holders.join(", "))?;
         if self.can_access {
             write!(f, " accessible")
         } else {
@@ -135,9 +234,45 @@ enum ArielPageKind {
     HomePage,
     SiteHomePage,
     SiteAmbient,
+    SiteForum,
+    SiteNews,
+    SiteCalendar,
+    /// A tool of a site that is not known.
+    SiteTool,
     Unknown,
 }
 
+/// The fields of a `tr` holding a thread or a forum post.
+#[derive(Debug, Default)]
+struct ArielRow {
+    /// The `id` of the `tr`, if any.
+    id: Option<String>,
+    title: String,
+    author: Option<String>,
+    date: Option<String>,
+    quote: Option<String>,
+    body: String,
+    /// Names and (relative) links of the files attached to the row.
+    attachments: Vec<(String, String)>,
+    /// Links of the lesson streams shown in the row.
+    videos: Vec<String>,
+}
+
+impl ArielRow {
+    /// The `id` of the row, or a hash of what it shows if it has none.
+    fn thread_id(&self) -> String {
+        self.id.clone().unwrap_or_else(|| {
+            let shown = format!(
+                "{}\n{}\n{}",
+                self.title,
+                self.author.as_deref().unwrap_or_default(),
+                self.date.as_deref().unwrap_or_default()
+            );
+            format!("{:016x}", fnv1a(shown.as_bytes()))
+        })
+    }
+}
+
 #[derive(Debug)]
 pub struct ArielPage {
     pub url: String,
@@ -146,11 +281,14 @@ pub struct ArielPage {
 }
 
 impl ArielPage {
-    pub fn from_raw(raw: String, url: String) -> anyhow::Result<ArielPage> {
+    pub fn from_raw(raw: String, url: String) -> crate::Result<ArielPage> {
         let mut options = tl::ParserOptions::new();
         options = options.track_ids();
         options = options.track_classes();
-        let soup = unsafe { tl::parse_owned(raw, options) }?;
+        let soup = unsafe { tl::parse_owned(raw, options) }.map_err(|e| crate::Error::Parse {
+            page: url.clone(),
+            reason: e.to_string(),
+        })?;
         let mut kind = ArielPageKind::Unknown;
         let parser = soup.get_ref().parser();
 
@@ -181,12 +319,25 @@ impl ArielPage {
                             if let tl::Node::Tag(child) = child {
                                 if let Some(Some(class)) = child.attributes().get("class") {
                                     if class == "active" {
-                                        let inner = child.inner_text(parser);
-                                        if inner.to_lowercase().contains("home") {
-                                            kind = ArielPageKind::SiteHomePage;
-                                        } else if inner.to_lowercase().contains("conten") {
-                                            kind = ArielPageKind::SiteAmbient;
-                                        }
+                                        let inner = child.inner_text(parser).to_lowercase();
+                                        let href = nav_link(child, parser)
+                                            .map(|(href, _)| href)
+                                            .unwrap_or_default();
+                                        kind = if inner.contains("home") {
+                                            ArielPageKind::SiteHomePage
+                                        } else {
+                                            match ArielToolKind::classify(&href, &inner) {
+                                                ArielToolKind::Contents => {
+                                                    ArielPageKind::SiteAmbient
+                                                }
+                                                ArielToolKind::Forum => ArielPageKind::SiteForum,
+                                                ArielToolKind::News => ArielPageKind::SiteNews,
+                                                ArielToolKind::Calendar => {
+                                                    ArielPageKind::SiteCalendar
+                                                }
+                                                ArielToolKind::Unknown => ArielPageKind::SiteTool,
+                                            }
+                                        };
                                     }
                                 }
                             }
@@ -217,7 +368,11 @@ impl ArielPage {
                 }
                 ret
             }
-            ArielPageKind::SiteAmbient => {
+            ArielPageKind::SiteAmbient
+            | ArielPageKind::SiteForum
+            | ArielPageKind::SiteNews
+            | ArielPageKind::SiteCalendar
+            | ArielPageKind::SiteTool => {
                 let parser = self.soup.get_ref().parser();
                 let mut site_title = String::new();
 
@@ -242,16 +397,21 @@ impl ArielPage {
         str
     }
 
-    pub fn get_children(&self) -> Vec<String> {
+    /// The URLs linked by the page to crawl next, trusting only the sites in
+    /// `sitemap` from the home page.
+    pub fn get_children(&self, sitemap: &ArielSitemap) -> Vec<String> {
         match self.kind {
-            ArielPageKind::HomePage => self.children_ariel_home(),
-            ArielPageKind::SiteHomePage => self.children_site_home_page(),
+            ArielPageKind::HomePage => self.children_ariel_home(sitemap),
+            ArielPageKind::SiteHomePage => self.children_site_home_page(sitemap),
             ArielPageKind::SiteAmbient => self.children_ambient(),
+            ArielPageKind::SiteForum => self.children_forum(),
+            ArielPageKind::SiteNews | ArielPageKind::SiteCalendar => vec![],
+            ArielPageKind::SiteTool => self.children_ambient(),
             ArielPageKind::Unknown => vec![],
         }
     }
 
-    fn children_ariel_home(&self) -> Vec<String> {
+    fn children_ariel_home(&self, sitemap: &ArielSitemap) -> Vec<String> {
         let parser = self.soup.get_ref().parser();
         let mut res = vec![];
         for ul in self
@@ -265,7 +425,7 @@ impl ArielPage {
                         if let tl::Node::Tag(child) = child {
                             if let Some(Some(href)) = child.attributes().get("href") {
                                 let href = href.as_utf8_str().to_string();
-                                if href.contains("ariel.ctu.unimi.it") {
+                                if sitemap.is_site_url(&href) {
                                     res.push(href);
                                 }
                             }
@@ -277,33 +437,57 @@ impl ArielPage {
         res
     }
 
-    fn children_site_home_page(&self) -> Vec<String> {
+    /// The tools of the site that are known to hold data, leaving out the
+    /// unknown ones (such as logging out) and those on other hosts.
+    fn children_site_home_page(&self, sitemap: &ArielSitemap) -> Vec<String> {
+        let mut res = vec![];
+        for tool in self.get_tools() {
+            if tool.kind == ArielToolKind::Unknown {
+                log::warn!("unknown tool {}, not visiting it", tool);
+            } else if !sitemap.is_site_url(&tool.url) {
+                log::warn!("tool {} is not on a site, not visiting it", tool);
+            } else {
+                res.push(tool.url);
+            }
+        }
+        res
+    }
+
+    /// The tools linked in the navigation bar of a site, except its home.
+    pub fn get_tools(&self) -> Vec<ArielTool> {
         let parser = self.soup.get_ref().parser();
-        let res = vec![];
+        let base = match self.url.parse::<url::Url>() {
+            Ok(base) => base,
+            Err(_) => return vec![],
+        };
+        let mut res: Vec<ArielTool> = vec![];
+
         for ul in self.soup.get_ref().get_elements_by_class_name("navbar-nav") {
             if let Some(ul) = ul.get(parser) {
                 if let Some(children) = ul.children() {
                     for child in children.all(parser) {
                         if let tl::Node::Tag(child) = child {
-                            if child.name() == "li" {
-                                for n in child.children().all(parser) {
-                                    if let tl::Node::Tag(n) = n {
-                                        if let Some(Some(href)) = n.attributes().get("href") {
-                                            let href = href.as_utf8_str().to_string();
-
-                                            if href.contains("toolName=conten") {
-                                                return vec![self
-                                                    .url
-                                                    .parse::<url::Url>()
-                                                    .unwrap()
-                                                    .join(&href)
-                                                    .unwrap()
-                                                    .to_string()];
-                                            }
-                                        }
-                                    }
-                                }
+                            if child.name() != "li" {
+                                continue;
+                            }
+                            let (href, name) = match nav_link(child, parser) {
+                                Some(link) => link,
+                                None => continue,
+                            };
+                            let url = match base.join(&href) {
+                                Ok(url) => url.to_string(),
+                                Err(_) => continue,
+                            };
+                            if url.to_lowercase().contains("/home/")
+                                || res.iter().any(|t| t.url == url)
+                            {
+                                continue;
                             }
+                            res.push(ArielTool {
+                                kind: ArielToolKind::classify(&href, &name),
+                                name,
+                                url,
+                            });
                         }
                     }
                 }
@@ -325,14 +509,7 @@ impl ArielPage {
                                     let href = href.as_utf8_str().to_string().replace("amp;", "");
                                     if href.contains("ThreadList") {
                                         log::info!("found threadlist {}", href);
-                                        res.push(
-                                            self.url
-                                                .parse::<url::Url>()
-                                                .unwrap()
-                                                .join(&href)
-                                                .unwrap()
-                                                .to_string(),
-                                        )
+                                        res.extend(self.join(&href));
                                     }
                                 }
                             }
@@ -341,132 +518,252 @@ impl ArielPage {
                 }
             }
         }
-        //        if let Some(tl) = self.soup.get_ref().get_element_by_id("threadList") {
-        //            if let Some(tl) = tl.get(parser) {
-        //
-        //            }
-        //        }
+        res
+    }
+
+    /// `href` relative to the page, or `None` (with a warning) if it is not a
+    /// valid link.
+    fn join(&self, href: &str) -> Option<String> {
+        match self.url.parse::<url::Url>().and_then(|url| url.join(href)) {
+            Ok(url) => Some(url.to_string()),
+            Err(e) => {
+                log::warn!("skipping bad link {:?} in {}: {}", href, self.url, e);
+                None
+            }
+        }
+    }
 
+    /// The discussions listed in a room of a forum, or the rooms of a forum,
+    /// like those of an ambient. Rooms link to each other, so that their list
+    /// is only followed outside of them.
+    fn children_forum(&self) -> Vec<String> {
+        let parser = self.soup.get_ref().parser();
+        let mut res = vec![];
+        let tl = match self.soup.get_ref().get_element_by_id("threadList") {
+            Some(tl) => tl,
+            None => return self.children_ambient(),
+        };
+        if let Some(tl) = tl.get(parser) {
+            if let Some(children) = tl.children() {
+                for child in children.all(parser) {
+                    if let tl::Node::Tag(child) = child {
+                        if let Some(Some(href)) = child.attributes().get("href") {
+                            let href = href.as_utf8_str().to_string().replace("amp;", "");
+                            if href.contains("MessageList") {
+                                log::info!("found discussion {}", href);
+                                res.extend(self.join(&href));
+                            }
+                        }
+                    }
+                }
+            }
+        }
         res
     }
 
     pub fn get_data(&self) -> Vec<ArielPageData> {
         let mut res = std::collections::HashMap::new();
-        let parser = self.soup.get_ref().parser();
+        let site = self.get_site_name();
+        let ambient = self.get_title();
+
+        for row in self.get_rows() {
+            let thread_id = row.thread_id();
+            log::info!("thread title is {}", row.title);
+            let files = row
+                .attachments
+                .iter()
+                .map(|(name, href)| (name.clone(), href, ArielPageDataKind::Generic));
+            let streams = row.videos.iter().map(|src| {
+                (
+                    format!("recording_{}", row.title),
+                    src,
+                    ArielPageDataKind::LessonStream,
+                )
+            });
+            for (name, href, kind) in files.chain(streams) {
+                let url = match self.join(href).map(|url| url.parse::<url::Url>()) {
+                    Some(Ok(url)) => url,
+                    _ => continue,
+                };
+                log::info!("pushing {}, {}, {:?}", name, url, kind);
+                res.entry(url.clone()).or_insert_with(|| ArielPageData {
+                    from_site: site.clone(),
+                    from_ambient: ambient.clone(),
+                    from_thread: row.title.clone(),
+                    thread_id: thread_id.clone(),
+                    name,
+                    url,
+                    kind,
+                });
+            }
+        }
+        let res = res.into_values().collect();
+        log::info!("{} produced {:?}", self.url, res);
+        res
+    }
+
+    /// The title, author, date and body of each thread in the page.
+    pub fn get_messages(&self) -> Vec<ArielThreadMessage> {
+        if let ArielPageKind::SiteForum | ArielPageKind::SiteNews = self.kind {
+            return vec![];
+        }
+
+        let site = self.get_site_name();
+        let ambient = self.get_title();
+        self.get_rows()
+            .into_iter()
+            .map(|row| ArielThreadMessage {
+                from_site: site.clone(),
+                from_ambient: ambient.clone(),
+                thread_id: row.thread_id(),
+                title: row.title,
+                author: row.author,
+                date: row.date,
+                body: row.body,
+            })
+            .collect()
+    }
+
+    /// The thread shown in this page, if it is a discussion of a forum.
+    pub fn get_forum_thread(&self) -> Option<ArielForumThread> {
+        if !self.is_forum() {
+            return None;
+        }
+
+        let base = self.url.parse::<url::Url>().ok()?;
+        let posts = self
+            .get_rows()
+            .into_iter()
+            .map(|row| ArielForumPost {
+                title: row.title,
+                author: row.author,
+                date: row.date,
+                quote: row.quote,
+                body: row.body,
+                attachments: row
+                    .attachments
+                    .into_iter()
+                    .filter_map(|(name, href)| {
+                        Some(ArielAttachment {
+                            name,
+                            url: base.join(&href).ok()?,
+                            kind: ArielPageDataKind::Generic,
+                        })
+                    })
+                    .collect(),
+            })
+            .collect::<Vec<_>>();
+
+        Some(ArielForumThread {
+            from_site: self.get_site_name(),
+            from_room: self.get_title(),
+            title: posts.first()?.title.clone(),
+            url: base,
+            posts,
+        })
+    }
+
+    /// The announcements shown in this page, if it is the news of a site.
+    pub fn get_announcements(&self) -> Vec<ArielAnnouncement> {
+        let url = match self.url.parse::<url::Url>() {
+            Ok(url) if self.is_news() => url,
+            _ => return vec![],
+        };
+
+        self.get_rows()
+            .into_iter()
+            .map(|row| ArielAnnouncement {
+                from_site: self.get_site_name(),
+                url: url.clone(),
+                title: row.title,
+                author: row.author,
+                published: row.date.as_deref().and_then(news::parse_date),
+                date: row.date,
+                body: row.body,
+            })
+            .collect()
+    }
+
+    pub fn is_site_home(&self) -> bool {
+        matches!(self.kind, ArielPageKind::SiteHomePage)
+    }
+
+    pub fn is_forum(&self) -> bool {
+        matches!(self.kind, ArielPageKind::SiteForum)
+    }
+
+    pub fn is_news(&self) -> bool {
+        matches!(self.kind, ArielPageKind::SiteNews)
+    }
+
+    pub fn is_calendar(&self) -> bool {
+        matches!(self.kind, ArielPageKind::SiteCalendar)
+    }
 
-        for child in self.soup.get_ref().children() {
-            if let Some(child) = child.get(parser) {
+    /// The threads or posts of the page, one for each titled `tr`.
+    fn get_rows(&self) -> Vec<ArielRow> {
+        let dom = self.soup.get_ref();
+        let parser = dom.parser();
+        let mut res = vec![];
+
+        for tr in dom.query_selector("tr").into_iter().flatten() {
+            let tr = match tr.get(parser) {
+                Some(tl::Node::Tag(tr)) => tr,
+                _ => continue,
+            };
+            let mut row = ArielRow {
+                id: match tr.attributes().get("id") {
+                    Some(Some(id)) => Some(id.as_utf8_str().to_string()),
+                    _ => None,
+                },
+                ..ArielRow::default()
+            };
+
+            for child in tr.children().all(parser) {
                 if let tl::Node::Tag(child) = child {
-                    if child.name() == "html" {
-                        for child in child.children().all(parser) {
-                            if let tl::Node::Tag(child) = child {
-                                log::debug!("\n\n\ndoing child {:?}\n\n\n", child);
-                                if child.name() == "tr" {
-                                    let mut title = String::new();
-
-                                    for child in child.children().all(parser) {
-                                        if let tl::Node::Tag(child) = child {
-                                            if child.name() == "h2" {
-                                                if let Some(Some(class)) =
-                                                    child.attributes().get("class")
-                                                {
-                                                    if class.as_utf8_str().contains("arielTitle") {
-                                                        title = child
-                                                            .inner_text(parser)
-                                                            .to_string()
-                                                            .trim()
-                                                            .to_string()
-                                                    }
-                                                }
-                                            }
-                                        }
-                                    }
-                                    log::info!("thread title is {}", title);
-                                    for child in child.children().all(parser) {
-                                        if let tl::Node::Tag(child) = child {
-                                            if let Some(Some(class)) =
-                                                child.attributes().get("class")
-                                            {
-                                                if class.as_utf8_str().contains("filename") {
-                                                    if let Some(Some(href)) =
-                                                        child.attributes().get("href")
-                                                    {
-                                                        let name =
-                                                            child.inner_text(parser).to_string();
-                                                        let url = self
-                                                            .url
-                                                            .parse::<url::Url>()
-                                                            .unwrap()
-                                                            .join(
-                                                                &href
-                                                                    .as_utf8_str()
-                                                                    .replace("amp;", ""),
-                                                            )
-                                                            .unwrap();
-                                                        let kind = ArielPageDataKind::Generic;
-                                                        log::info!(
-                                                            "pushing {}, {}, {:?}",
-                                                            name,
-                                                            url,
-                                                            kind
-                                                        );
-                                                        let pagedata = ArielPageData {
-                                                            from_site: self.get_site_name(),
-                                                            from_ambient: self.get_title(),
-                                                            from_thread: title.clone(),
-                                                            name,
-                                                            url,
-                                                            kind,
-                                                        };
-                                                        if !res.contains_key(&pagedata.url) {
-                                                            res.insert(
-                                                                pagedata.url.clone(),
-                                                                pagedata,
-                                                            );
-                                                        }
-                                                    }
-                                                }
-                                            } else if let Some(Some(r#type)) =
-                                                child.attributes().get("type")
-                                            {
-                                                if r#type.as_utf8_str().contains("video") {
-                                                    if let Some(Some(href)) =
-                                                        child.attributes().get("src")
-                                                    {
-                                                        let url = href
-                                                            .as_utf8_str()
-                                                            .parse::<url::Url>()
-                                                            .unwrap();
-
-                                                        log::info!("pushing {}", url);
-                                                        let pagedata = ArielPageData {
-                                                            from_site: self.get_site_name(),
-                                                            from_ambient: self.get_title(),
-                                                            from_thread: title.clone(),
-                                                            name: format!("recording_{}", title),
-                                                            url,
-                                                            kind: ArielPageDataKind::LessonStream,
-                                                        };
-                                                        if !res.contains_key(&pagedata.url) {
-                                                            res.insert(
-                                                                pagedata.url.clone(),
-                                                                pagedata,
-                                                            );
-                                                        }
-                                                    }
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                            }
+                    if let (Some(Some(r#type)), Some(Some(src))) = (
+                        child.attributes().get("type"),
+                        child.attributes().get("src"),
+                    ) {
+                        if r#type.as_utf8_str().contains("video") {
+                            row.videos.push(src.as_utf8_str().replace("amp;", ""));
+                        }
+                    }
+                    let class = match child.attributes().get("class") {
+                        Some(Some(class)) => class.as_utf8_str().to_lowercase(),
+                        _ => continue,
+                    };
+                    let text = || {
+                        child
+                            .inner_text(parser)
+                            .split_whitespace()
+                            .collect::<Vec<_>>()
+                            .join(" ")
+                    };
+
+                    if child.name() == "h2" && class.contains("arieltitle") {
+                        row.title = text();
+                    } else if class.contains("author") && row.author.is_none() {
+                        row.author = Some(text());
+                    } else if class.contains("date") && row.date.is_none() {
+                        row.date = Some(text());
+                    } else if class.contains("quote") && row.quote.is_none() {
+                        row.quote = Some(child.inner_html(parser).trim().to_string());
+                    } else if class.contains("messagebody") || class.contains("postbody") {
+                        row.body.push_str(child.inner_html(parser).trim());
+                    } else if class.contains("filename") {
+                        if let Some(Some(href)) = child.attributes().get("href") {
+                            row.attachments
+                                .push((text(), href.as_utf8_str().replace("amp;", "")));
                         }
                     }
                 }
             }
+
+            if !row.title.is_empty() {
+                res.push(row);
+            }
         }
-        let res = res.values().cloned().collect();
-        log::info!("{} produced {:?}", self.url, res);
         res
     }
 
@@ -486,28 +783,107 @@ impl ArielPage {
     }
 }
 
+/// The link and the label of an entry of the navigation bar.
+fn nav_link(li: &tl::HTMLTag, parser: &tl::Parser) -> Option<(String, String)> {
+    li.children().all(parser).iter().find_map(|n| match n {
+        tl::Node::Tag(a) => match a.attributes().get("href") {
+            Some(Some(href)) => Some((
+                href.as_utf8_str().replace("amp;", ""),
+                a.inner_text(parser).trim().to_string(),
+            )),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
 impl std::fmt::Display for ArielPage {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "[{}] - {}", self.get_site_name(), self.get_title())
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub enum ArielPageDataKind {
     LessonStream,
     Generic,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct ArielPageData {
     pub from_site: String,
     pub from_ambient: String,
     pub from_thread: String,
+    /// The id of the thread, see [`ArielThreadMessage::thread_id`].
+    #[serde(default)]
+    pub thread_id: String,
     pub name: String,
     pub url: Url,
     pub kind: ArielPageDataKind,
 }
 
+/// The text of a thread, as written by its author.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ArielThreadMessage {
+    pub from_site: String,
+    pub from_ambient: String,
+    /// Tells apart threads with the same title.
+    pub thread_id: String,
+    pub title: String,
+    pub author: Option<String>,
+    pub date: Option<String>,
+    /// The body of the message, as HTML.
+    pub body: String,
+}
+
+impl ArielThreadMessage {
+    pub fn to_markdown(&self) -> String {
+        let mut md = format!("# {}\n\n", self.title);
+        match (&self.author, &self.date) {
+            (Some(author), Some(date)) => md.push_str(&format!("*{}, {}*\n\n", author, date)),
+            (Some(line), None) | (None, Some(line)) => md.push_str(&format!("*{}*\n\n", line)),
+            (None, None) => {}
+        }
+        let body = html2md::parse_html(&self.body);
+        if !body.trim().is_empty() {
+            md.push_str(body.trim());
+            md.push('\n');
+        }
+        md
+    }
+
+    /// Where the message is saved, next to the attachments of its thread.
+    pub fn get_path(&self, base: &str) -> PathBuf {
+        let mut path = thread_dir(base, &self.from_site, &self.from_ambient, &self.title);
+        path.push(format!("message_{}.md", heck::AsSnakeCase(&self.thread_id)));
+        path
+    }
+
+    /// Save the message as Markdown under `base`, returning its path.
+    pub fn save(&self, base: &str) -> crate::Result<PathBuf> {
+        let path = self.get_path(base);
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        std::fs::write(&path, self.to_markdown())?;
+        Ok(path)
+    }
+}
+
+/// The 64 bit FNV-1a hash of `bytes`, which unlike the hashers of the
+/// standard library is the same on every platform and Rust release.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn thread_dir(base: &str, site: &str, ambient: &str, thread: &str) -> PathBuf {
+    let mut path = PathBuf::from(base);
+    path.push(heck::AsSnakeCase(site).to_string());
+    path.push(heck::AsSnakeCase(ambient).to_string());
+    path.push(heck::AsSnakeCase(thread).to_string());
+    path
+}
+
 impl std::fmt::Display for ArielPageData {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{} (url: {})", self.get_name(), self.url)
@@ -518,8 +894,8 @@ impl ArielPageData {
     pub fn get_name(&self) -> String {
         match self.kind {
             ArielPageDataKind::LessonStream => {
-                let rand_id = rand::random::<i32>();
-                let filename = format!("{}{rand_id}", self.name);
+                let url_id = fnv1a(self.url.as_str().as_bytes());
+                let filename = format!("{}{url_id}", self.name);
 
                 let title_regex = regex::Regex::new(r".*/vod/(.+):(.+)/manifest.m3u8").unwrap();
                 if let Some(matches) = title_regex.captures(&self.url.to_string()) {
@@ -547,4 +923,10 @@ impl ArielPageData {
             ArielPageDataKind::Generic => self.name.clone(),
         }
     }
+
+    pub fn get_path(&self, base: &str) -> PathBuf {
+        let mut path = thread_dir(base, &self.from_site, &self.from_ambient, &self.from_thread);
+        path.push(self.get_name());
+        path
+    }
 }
@@ -0,0 +1,160 @@
+use std::path::PathBuf;
+
+use futures::StreamExt;
+
+use super::{
+    manifest::{ArielManifest, ArielRemoteVersion},
+    page::ArielPageData,
+    ArielNavigator,
+};
+
+/// What happens to the items of an [`ArielDownloadQueue`], identified by their
+/// index in the list passed to [`ArielDownloadQueue::run`].
+#[derive(Debug, Clone)]
+pub enum ArielDownloadEvent {
+    Started {
+        index: usize,
+        name: String,
+        size: u64,
+    },
+    Progress {
+        index: usize,
+        bytes: u64,
+    },
+    Finished {
+        index: usize,
+        path: PathBuf,
+        /// What the server told about the item before downloading it.
+        version: ArielRemoteVersion,
+    },
+    Failed {
+        index: usize,
+        error: String,
+    },
+}
+
+/// Downloads many items at once, running at most `jobs` of them in parallel
+/// with the same (authenticated) navigator.
+#[derive(Debug, Clone)]
+pub struct ArielDownloadQueue {
+    navigator: ArielNavigator,
+    jobs: usize,
+}
+
+impl ArielDownloadQueue {
+    pub fn new(navigator: ArielNavigator, jobs: usize) -> Self {
+        ArielDownloadQueue {
+            navigator,
+            jobs: jobs.max(1),
+        }
+    }
+
+    /// The items of `data` that are not in `manifest` or changed since they
+    /// were downloaded, asking the server about `jobs` of them at a time.
+    ///
+    /// Each item comes with what the server told about it, to be passed on to
+    /// [`ArielDownloadQueue::run`]; items the server could not tell about are
+    /// kept as changed, without a version.
+    pub async fn changed(
+        &self,
+        manifest: &ArielManifest,
+        data: Vec<ArielPageData>,
+    ) -> Vec<(ArielPageData, Option<ArielRemoteVersion>)> {
+        let versions = futures::stream::iter(&data)
+            .map(|d| self.navigator.get_version(d))
+            .buffered(self.jobs)
+            .collect::<Vec<_>>()
+            .await;
+
+        let mut changed = vec![];
+        for (d, version) in data.into_iter().zip(versions) {
+            match version {
+                Ok(version) if manifest.is_up_to_date(&d, &version) => {
+                    log::info!("{} is up to date, skipping", d);
+                }
+                Ok(version) => changed.push((d, Some(version))),
+                Err(e) => {
+                    log::warn!("could not check whether {} changed: {}", d, e);
+                    changed.push((d, None));
+                }
+            }
+        }
+        changed
+    }
+
+    /// Download every item of `data` under `path`, returning where each item
+    /// was saved (or why it failed) in the order the downloads complete.
+    ///
+    /// The server is asked about the items that come without a version.
+    pub async fn run(
+        &self,
+        path: String,
+        data: Vec<(ArielPageData, Option<ArielRemoteVersion>)>,
+        events: std::sync::mpsc::Sender<ArielDownloadEvent>,
+    ) -> Vec<(ArielPageData, crate::Result<PathBuf>)> {
+        futures::stream::iter(data.into_iter().enumerate())
+            .map(|(index, (d, version))| {
+                let path = path.clone();
+                let events = events.clone();
+                async move {
+                    let res = self
+                        .download_one(index, path, &d, version, events.clone())
+                        .await;
+                    let event = match &res {
+                        Ok((path, version)) => ArielDownloadEvent::Finished {
+                            index,
+                            path: path.clone(),
+                            version: version.clone(),
+                        },
+                        Err(e) => ArielDownloadEvent::Failed {
+                            index,
+                            error: e.to_string(),
+                        },
+                    };
+                    let _ = events.send(event);
+                    (d, res.map(|(path, _)| path))
+                }
+            })
+            .buffer_unordered(self.jobs)
+            .collect()
+            .await
+    }
+
+    async fn download_one(
+        &self,
+        index: usize,
+        path: String,
+        data: &ArielPageData,
+        version: Option<ArielRemoteVersion>,
+        events: std::sync::mpsc::Sender<ArielDownloadEvent>,
+    ) -> crate::Result<(PathBuf, ArielRemoteVersion)> {
+        let version = match version {
+            Some(version) => version,
+            None => self.navigator.get_version(data).await?,
+        };
+        events.send(ArielDownloadEvent::Started {
+            index,
+            name: data.get_name(),
+            size: version.size,
+        })?;
+
+        let (chunk_done_size_px, mut chunk_done_size_cx) = tokio::sync::mpsc::unbounded_channel();
+        let forward = async move {
+            while let Some(bytes) = chunk_done_size_cx.recv().await {
+                if events
+                    .send(ArielDownloadEvent::Progress { index, bytes })
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        };
+
+        let (res, ()) = futures::join!(
+            self.navigator
+                .download(path, data.clone(), chunk_done_size_px),
+            forward
+        );
+        Ok((res?, version))
+    }
+}
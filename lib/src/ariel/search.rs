@@ -0,0 +1,125 @@
+use super::page::ArielTitlePage;
+use crate::Error;
+
+/// Which search results to keep, depending on whether they can be accessed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ArielAccessFilter {
+    #[default]
+    Accessible,
+    Closed,
+    Any,
+}
+
+impl std::str::FromStr for ArielAccessFilter {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "accessible" => Ok(ArielAccessFilter::Accessible),
+            "closed" => Ok(ArielAccessFilter::Closed),
+            "any" => Ok(ArielAccessFilter::Any),
+            _ => Err(Error::InvalidValue {
+                value: s.to_string(),
+                expected: "'accessible', 'closed' or 'any'".to_string(),
+            }),
+        }
+    }
+}
+
+impl std::fmt::Display for ArielAccessFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArielAccessFilter::Accessible => write!(f, "accessible"),
+            ArielAccessFilter::Closed => write!(f, "closed"),
+            ArielAccessFilter::Any => write!(f, "any"),
+        }
+    }
+}
+
+/// The filters to apply to search results.
+#[derive(Debug, Clone, Default)]
+pub struct ArielSearchFilter {
+    /// Part of the name or the identifier of one of the holders.
+    pub teacher: Option<String>,
+    /// Part of the academic year, such as `2022` or `2021/2022`.
+    pub year: Option<String>,
+    pub access: ArielAccessFilter,
+}
+
+impl ArielSearchFilter {
+    pub fn matches(&self, page: &ArielTitlePage) -> bool {
+        let access = match self.access {
+            ArielAccessFilter::Accessible => page.can_access,
+            ArielAccessFilter::Closed => !page.can_access,
+            ArielAccessFilter::Any => true,
+        };
+        let teacher = match &self.teacher {
+            Some(teacher) => page.holders.iter().any(|h| h.matches(teacher)),
+            None => true,
+        };
+        let year = match (&self.year, &page.academic_year) {
+            (Some(year), Some(page_year)) => page_year.contains(&year.replace('-', "/")),
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+        access && teacher && year
+    }
+
+    pub fn apply(&self, pages: Vec<ArielTitlePage>) -> Vec<ArielTitlePage> {
+        pages.into_iter().filter(|p| self.matches(p)).collect()
+    }
+}
+
+/// How to sort search results. Results missing the field sorted by come last.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ArielSearchSort {
+    #[default]
+    Title,
+    Year,
+    Code,
+    Teacher,
+}
+
+impl ArielSearchSort {
+    pub fn sort(&self, pages: &mut [ArielTitlePage]) {
+        match self {
+            ArielSearchSort::Title => pages.sort_by_key(|p| p.title.to_lowercase()),
+            ArielSearchSort::Year => pages.sort_by_key(|p| last_if_none(p.academic_year.clone())),
+            ArielSearchSort::Code => pages.sort_by_key(|p| last_if_none(p.code.clone())),
+            ArielSearchSort::Teacher => pages
+                .sort_by_key(|p| last_if_none(p.holders.first().map(|h| h.name.to_lowercase()))),
+        }
+    }
+}
+
+fn last_if_none(key: Option<String>) -> (bool, Option<String>) {
+    (key.is_none(), key)
+}
+
+impl std::str::FromStr for ArielSearchSort {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "title" => Ok(ArielSearchSort::Title),
+            "year" => Ok(ArielSearchSort::Year),
+            "code" => Ok(ArielSearchSort::Code),
+            "teacher" => Ok(ArielSearchSort::Teacher),
+            _ => Err(Error::InvalidValue {
+                value: s.to_string(),
+                expected: "'title', 'year', 'code' or 'teacher'".to_string(),
+            }),
+        }
+    }
+}
+
+impl std::fmt::Display for ArielSearchSort {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArielSearchSort::Title => write!(f, "title"),
+            ArielSearchSort::Year => write!(f, "year"),
+            ArielSearchSort::Code => write!(f, "code"),
+            ArielSearchSort::Teacher => write!(f, "teacher"),
+        }
+    }
+}
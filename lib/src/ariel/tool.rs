@@ -0,0 +1,64 @@
+/// What a tool in the navigation bar of a site is for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArielToolKind {
+    Contents,
+    Forum,
+    News,
+    Calendar,
+    Unknown,
+}
+
+impl ArielToolKind {
+    /// Classify a tool from the `toolName` in its link or, if missing, from
+    /// its label.
+    pub fn classify(href: &str, label: &str) -> ArielToolKind {
+        let name = tool_name(href).unwrap_or_else(|| label.to_lowercase());
+        if name.contains("conten") {
+            ArielToolKind::Contents
+        } else if name.contains("forum") {
+            ArielToolKind::Forum
+        } else if name.contains("avvis") || name.contains("news") {
+            ArielToolKind::News
+        } else if name.contains("calend") || name.contains("agenda") {
+            ArielToolKind::Calendar
+        } else {
+            ArielToolKind::Unknown
+        }
+    }
+}
+
+impl std::fmt::Display for ArielToolKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArielToolKind::Contents => write!(f, "contents"),
+            ArielToolKind::Forum => write!(f, "forum"),
+            ArielToolKind::News => write!(f, "news"),
+            ArielToolKind::Calendar => write!(f, "calendar"),
+            ArielToolKind::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+/// A tool of a site, as linked in its navigation bar.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ArielTool {
+    pub name: String,
+    pub url: String,
+    pub kind: ArielToolKind,
+}
+
+impl std::fmt::Display for ArielTool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} [{}] ({})", self.name, self.kind, self.url)
+    }
+}
+
+fn tool_name(href: &str) -> Option<String> {
+    let (_, query) = href.split_once('?')?;
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| key.eq_ignore_ascii_case("toolName"))
+        .map(|(_, value)| value.to_lowercase())
+}
@@ -0,0 +1,137 @@
+use std::collections::BTreeMap;
+
+use url::Url;
+
+use super::{
+    page::{ArielPageData, ArielPageDataKind, ArielThreadMessage},
+    tool::ArielTool,
+};
+
+/// The structure of an Ariel site, as found by crawling it.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ArielSite {
+    pub title: String,
+    pub url: Url,
+    /// Every tool linked by the site, including those not crawled.
+    #[serde(default)]
+    pub tools: Vec<ArielTool>,
+    pub ambients: Vec<ArielAmbient>,
+}
+
+/// A room of a site listing threads, such as the lessons of a course.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ArielAmbient {
+    pub title: String,
+    pub url: Url,
+    pub threads: Vec<ArielThread>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ArielThread {
+    /// Tells apart threads with the same title.
+    #[serde(default)]
+    pub id: String,
+    pub title: String,
+    #[serde(default)]
+    pub author: Option<String>,
+    #[serde(default)]
+    pub date: Option<String>,
+    /// The body of the thread, as HTML.
+    #[serde(default)]
+    pub body: String,
+    pub attachments: Vec<ArielAttachment>,
+}
+
+/// A file or a lesson stream attached to a thread.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ArielAttachment {
+    pub name: String,
+    pub url: Url,
+    pub kind: ArielPageDataKind,
+}
+
+impl ArielSite {
+    /// All the attachments of the site, ready to be downloaded.
+    pub fn data(&self) -> Vec<ArielPageData> {
+        let mut res = vec![];
+        for ambient in &self.ambients {
+            for thread in &ambient.threads {
+                for attachment in &thread.attachments {
+                    res.push(ArielPageData {
+                        from_site: self.title.clone(),
+                        from_ambient: ambient.title.clone(),
+                        from_thread: thread.title.clone(),
+                        thread_id: thread.id.clone(),
+                        name: attachment.name.clone(),
+                        url: attachment.url.clone(),
+                        kind: attachment.kind.clone(),
+                    });
+                }
+            }
+        }
+        res
+    }
+}
+
+impl ArielAmbient {
+    /// Group `data` and `messages` found in the ambient at `url` by thread.
+    /// Threads are sorted by title, then id, and attachments by URL, so that
+    /// two crawls of an unchanged ambient give the same tree.
+    pub fn from_data(
+        title: String,
+        url: Url,
+        data: Vec<ArielPageData>,
+        messages: Vec<ArielThreadMessage>,
+    ) -> ArielAmbient {
+        let mut threads = BTreeMap::new();
+        for message in messages {
+            let thread = ArielThread::entry(&mut threads, message.thread_id, message.title);
+            thread.author = message.author;
+            thread.date = message.date;
+            thread.body = message.body;
+        }
+        for d in data {
+            let thread = ArielThread::entry(&mut threads, d.thread_id, d.from_thread);
+            thread.attachments.push(ArielAttachment {
+                name: d.name,
+                url: d.url,
+                kind: d.kind,
+            });
+        }
+
+        let threads = threads
+            .into_values()
+            .map(|mut thread| {
+                thread
+                    .attachments
+                    .sort_by(|a, b| a.url.as_str().cmp(b.url.as_str()));
+                thread
+            })
+            .collect();
+
+        ArielAmbient {
+            title,
+            url,
+            threads,
+        }
+    }
+}
+
+impl ArielThread {
+    fn entry(
+        threads: &mut BTreeMap<(String, String), ArielThread>,
+        id: String,
+        title: String,
+    ) -> &mut ArielThread {
+        threads
+            .entry((title.clone(), id.clone()))
+            .or_insert_with(|| ArielThread {
+                id,
+                title,
+                author: None,
+                date: None,
+                body: String::new(),
+                attachments: vec![],
+            })
+    }
+}
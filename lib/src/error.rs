@@ -0,0 +1,79 @@
+use std::path::PathBuf;
+
+/// Everything that can go wrong while talking to UniMi services.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("invalid username or password")]
+    InvalidCredentials,
+
+    #[error("the session has expired")]
+    SessionExpired,
+
+    #[error("request to '{url}' failed with status {status}")]
+    Http {
+        status: reqwest::StatusCode,
+        url: String,
+    },
+
+    #[error("network error: {0}")]
+    Network(#[from] reqwest::Error),
+
+    #[error("invalid network settings: {0}")]
+    NetworkSettings(String),
+
+    #[error("no answer from '{url}' within {secs}s")]
+    Timeout { url: String, secs: u64 },
+
+    #[error("could not parse {page}: {reason}")]
+    Parse { page: String, reason: String },
+
+    #[error("invalid or unsupported stream '{url}': {reason}")]
+    Stream { url: String, reason: String },
+
+    #[error("could not remux {path:?}: {reason}")]
+    Remux { path: PathBuf, reason: String },
+
+    #[error("invalid url: {0}")]
+    Url(#[from] url::ParseError),
+
+    #[error("invalid value '{value}', expected {expected}")]
+    InvalidValue { value: String, expected: String },
+
+    #[error("could not open the credential vault {path:?}: {reason}")]
+    Vault { path: PathBuf, reason: String },
+
+    #[error("could not read the password from '{from}': {reason}")]
+    PasswordSource { from: String, reason: String },
+
+    #[error("nobody is listening for progress anymore")]
+    ProgressClosed,
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+impl Error {
+    /// Whether the error may go away by trying again, like a dropped
+    /// connection or a timeout.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::Network(e) => e.is_connect() || e.is_timeout() || e.is_request() || e.is_body(),
+            Error::Timeout { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+impl<T> From<std::sync::mpsc::SendError<T>> for Error {
+    fn from(_: std::sync::mpsc::SendError<T>) -> Self {
+        Error::ProgressClosed
+    }
+}
+
+impl<T> From<tokio::sync::mpsc::error::SendError<T>> for Error {
+    fn from(_: tokio::sync::mpsc::error::SendError<T>) -> Self {
+        Error::ProgressClosed
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
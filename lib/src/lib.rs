@@ -1,4 +1,10 @@
 pub mod ariel;
+mod error;
+pub mod net;
+pub mod password;
 pub mod time;
 pub mod ugov;
 pub mod unimia;
+pub mod vault;
+
+pub use error::{Error, Result};
@@ -0,0 +1,197 @@
+use std::{
+    path::PathBuf,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::Error;
+
+/// How to reach the network, for campus networks and the like.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct NetworkConfig {
+    /// An `http://`, `https://` or `socks5://` proxy for every request.
+    pub proxy: Option<String>,
+    /// Seconds to wait for a connection to be established.
+    pub connect_timeout: Option<u64>,
+    /// Seconds to wait for the server to send something before giving up.
+    pub read_timeout: Option<u64>,
+    /// A PEM file with certificates to trust besides those of the system.
+    pub ca_bundle: Option<PathBuf>,
+    /// The user agent sent with every request.
+    pub user_agent: Option<String>,
+    /// How many requests to send each second at most.
+    pub requests_per_second: Option<u32>,
+    /// How many requests can be waiting for an answer at the same time.
+    pub max_concurrent_requests: Option<usize>,
+    /// How many bytes per second all the downloads together can take.
+    pub max_download_rate: Option<u64>,
+    /// How failed requests are tried again.
+    pub retry: RetryPolicy,
+}
+
+/// How requests failing for reasons that may go away by themselves, such as a
+/// dropped connection or an overloaded server, are tried again.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// How many times a request is tried at most, the first one included.
+    pub max_attempts: u32,
+    /// Milliseconds to wait before the first retry, doubled at every next one.
+    pub initial_backoff_ms: u64,
+    /// The longest wait between two attempts, in milliseconds. A server
+    /// asking with `Retry-After` to wait longer than this is not retried.
+    pub max_backoff_ms: u64,
+    /// The statuses worth trying again on.
+    pub retry_statuses: Vec<u16>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 4,
+            initial_backoff_ms: 500,
+            max_backoff_ms: 30_000,
+            retry_statuses: vec![408, 429, 500, 502, 503, 504],
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn retries_status(&self, status: u16) -> bool {
+        self.retry_statuses.contains(&status)
+    }
+
+    /// How long to wait after the `attempt`-th failed attempt (starting from
+    /// 1): the exponential backoff, of which a random half is kept so that
+    /// parallel downloads do not retry all at once.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exp = 1u64
+            .checked_shl(attempt.saturating_sub(1))
+            .unwrap_or(u64::MAX);
+        let backoff = self
+            .initial_backoff_ms
+            .saturating_mul(exp)
+            .min(self.max_backoff_ms);
+        Duration::from_millis(backoff / 2 + fastrand::u64(..=backoff / 2))
+    }
+
+    pub fn max_backoff(&self) -> Duration {
+        Duration::from_millis(self.max_backoff_ms)
+    }
+}
+
+/// Keeps the requests and the downloads within the limits of a
+/// [`NetworkConfig`], not to get throttled by the servers.
+#[derive(Debug)]
+pub struct RateLimiter {
+    turns: Option<tokio::sync::Semaphore>,
+    request_interval: Option<Duration>,
+    next_request: Mutex<Instant>,
+    bytes_per_second: Option<u64>,
+    next_bytes: Mutex<Instant>,
+}
+
+/// A turn to send a request, which counts as in flight until this is dropped.
+pub type RequestTurn<'a> = Option<tokio::sync::SemaphorePermit<'a>>;
+
+impl RateLimiter {
+    pub fn new(config: &NetworkConfig) -> RateLimiter {
+        RateLimiter {
+            turns: config
+                .max_concurrent_requests
+                .filter(|&n| n > 0)
+                .map(tokio::sync::Semaphore::new),
+            request_interval: config
+                .requests_per_second
+                .filter(|&n| n > 0)
+                .map(|n| Duration::from_secs(1) / n),
+            next_request: Mutex::new(Instant::now()),
+            bytes_per_second: config.max_download_rate.filter(|&n| n > 0),
+            next_bytes: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Wait until fewer requests than allowed are in flight and enough time
+    /// passed since the last one was sent.
+    pub async fn request(&self) -> RequestTurn<'_> {
+        let turn = match &self.turns {
+            Some(turns) => Some(turns.acquire().await.expect("semaphore is never closed")),
+            None => None,
+        };
+        if let Some(interval) = self.request_interval {
+            let wait = Self::book(&self.next_request, interval);
+            tokio::time::sleep(wait).await;
+        }
+        turn
+    }
+
+    /// Wait as long as it takes for `bytes` more downloaded bytes to stay
+    /// within the bandwidth cap.
+    pub async fn downloaded(&self, bytes: u64) {
+        if let Some(rate) = self.bytes_per_second {
+            let time = Duration::from_secs_f64(bytes as f64 / rate as f64);
+            let wait = Self::book(&self.next_bytes, time);
+            tokio::time::sleep(wait + time).await;
+        }
+    }
+
+    /// Book `time` starting from the end of the last booking, returning how
+    /// long until it starts.
+    fn book(next: &Mutex<Instant>, time: Duration) -> Duration {
+        let now = Instant::now();
+        let mut next = next.lock().unwrap();
+        let start = (*next).max(now);
+        *next = start + time;
+        start - now
+    }
+}
+
+impl NetworkConfig {
+    pub const DEFAULT_USER_AGENT: &'static str = "Sebastian";
+
+    pub fn is_default(&self) -> bool {
+        *self == NetworkConfig::default()
+    }
+
+    pub fn read_timeout(&self) -> Option<Duration> {
+        self.read_timeout.map(Duration::from_secs)
+    }
+
+    /// A client builder with these settings applied.
+    pub fn client_builder(&self) -> crate::Result<reqwest::ClientBuilder> {
+        let user_agent = self
+            .user_agent
+            .as_deref()
+            .unwrap_or(Self::DEFAULT_USER_AGENT);
+        let mut builder = reqwest::Client::builder().user_agent(user_agent);
+
+        if let Some(proxy) = &self.proxy {
+            let proxy = reqwest::Proxy::all(proxy.as_str())
+                .map_err(|e| Error::NetworkSettings(format!("invalid proxy '{}': {}", proxy, e)))?;
+            builder = builder.proxy(proxy);
+        }
+        if let Some(secs) = self.connect_timeout {
+            builder = builder.connect_timeout(Duration::from_secs(secs));
+        }
+        if let Some(path) = &self.ca_bundle {
+            let pem = std::fs::read(path).map_err(|e| {
+                Error::NetworkSettings(format!("could not read CA bundle {:?}: {}", path, e))
+            })?;
+            let certs = reqwest::Certificate::from_pem_bundle(&pem).map_err(|e| {
+                Error::NetworkSettings(format!("invalid CA bundle {:?}: {}", path, e))
+            })?;
+            if certs.is_empty() {
+                return Err(Error::NetworkSettings(format!(
+                    "no certificates in CA bundle {:?}",
+                    path
+                )));
+            }
+            for cert in certs {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+
+        Ok(builder)
+    }
+}
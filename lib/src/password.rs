@@ -0,0 +1,176 @@
+use std::path::PathBuf;
+
+use zeroize::Zeroize;
+
+use crate::Error;
+
+/// A string such as a password, printed as `***` so that it never ends up in
+/// logs, and zeroed when dropped.
+#[derive(Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
+pub struct SecretString(String);
+
+impl SecretString {
+    /// The secret itself, only to be used where it is sent or stored.
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl Drop for SecretString {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(secret: String) -> Self {
+        SecretString(secret)
+    }
+}
+
+impl From<&str> for SecretString {
+    fn from(secret: &str) -> Self {
+        SecretString(secret.to_string())
+    }
+}
+
+impl std::str::FromStr for SecretString {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(s.into())
+    }
+}
+
+impl std::fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "***")
+    }
+}
+
+impl std::fmt::Display for SecretString {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "***")
+    }
+}
+
+/// Show the fields of a form without their values, for logging.
+pub fn redact_form(form: &[(String, String)]) -> String {
+    form.iter()
+        .map(|(key, _)| format!("{}=***", key))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Where to read a password from when running, so that it never has to be
+/// written in the configuration. Written as `command:<shell command>`,
+/// `env:<variable>` or `file:<path>`.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum PasswordSource {
+    /// The output of a shell command, such as `pass show unimi`.
+    Command(String),
+    /// The value of an environment variable.
+    Env(String),
+    /// The content of a file.
+    File(PathBuf),
+}
+
+impl PasswordSource {
+    /// Read the password, without the line ending a command or a file usually
+    /// end with.
+    pub fn resolve(&self) -> crate::Result<SecretString> {
+        let error = |reason: String| Error::PasswordSource {
+            from: self.to_string(),
+            reason,
+        };
+
+        let mut password = match self {
+            PasswordSource::Command(command) => {
+                #[cfg(windows)]
+                let output = std::process::Command::new("cmd")
+                    .args(["/C", command])
+                    .output();
+                #[cfg(not(windows))]
+                let output = std::process::Command::new("sh")
+                    .args(["-c", command])
+                    .output();
+                let output = output.map_err(|e| error(e.to_string()))?;
+                if !output.status.success() {
+                    let stderr = String::from_utf8_lossy(&output.stderr);
+                    let reason = match stderr.trim() {
+                        "" => format!("the command failed with {}", output.status),
+                        stderr => format!("the command failed with {}: {}", output.status, stderr),
+                    };
+                    return Err(error(reason));
+                }
+                String::from_utf8(output.stdout).map_err(|e| error(e.to_string()))?
+            }
+            PasswordSource::Env(var) => std::env::var(var).map_err(|e| error(e.to_string()))?,
+            PasswordSource::File(path) => {
+                std::fs::read_to_string(path).map_err(|e| error(e.to_string()))?
+            }
+        };
+
+        for ending in ["\n", "\r"] {
+            if password.ends_with(ending) {
+                password.pop();
+            }
+        }
+        if password.is_empty() {
+            return Err(error("the password is empty".to_string()));
+        }
+        Ok(password.into())
+    }
+}
+
+impl std::str::FromStr for PasswordSource {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, value) = s.split_once(':').unwrap_or((s, ""));
+        let value = value.trim();
+        match kind.trim().to_lowercase().as_str() {
+            "command" | "cmd" if !value.is_empty() => Ok(PasswordSource::Command(value.into())),
+            "env" if !value.is_empty() => Ok(PasswordSource::Env(value.into())),
+            "file" if !value.is_empty() => Ok(PasswordSource::File(value.into())),
+            _ => Err(Error::InvalidValue {
+                value: s.to_string(),
+                expected: "'command:<command>', 'env:<variable>' or 'file:<path>'".to_string(),
+            }),
+        }
+    }
+}
+
+impl std::fmt::Display for PasswordSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PasswordSource::Command(command) => write!(f, "command:{}", command),
+            PasswordSource::Env(var) => write!(f, "env:{}", var),
+            PasswordSource::File(path) => write!(f, "file:{}", path.display()),
+        }
+    }
+}
+
+impl TryFrom<String> for PasswordSource {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<PasswordSource> for String {
+    fn from(source: PasswordSource) -> Self {
+        source.to_string()
+    }
+}
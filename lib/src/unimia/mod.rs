@@ -2,9 +2,20 @@
 
 use serde::{Deserialize, Serialize};
 
+use crate::password::SecretString;
+
 /// This struct is the general configuration used to access UniMia.
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct UnimiaUserConfig {
     pub username: String,
-    pub password: String,
+    #[serde(default, skip_serializing_if = "SecretString::is_empty")]
+    pub password: SecretString,
+    /// The name of the entry of the credential vault holding the password,
+    /// which is then never saved in the configuration.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credential: Option<String>,
+    /// Where to read the password from at every run, which is then never
+    /// saved in the configuration.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_from: Option<crate::password::PasswordSource>,
 }
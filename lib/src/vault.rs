@@ -0,0 +1,153 @@
+use std::{collections::BTreeMap, path::Path};
+
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Nonce,
+};
+use argon2::password_hash::rand_core::RngCore;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use zeroize::Zeroizing;
+
+use crate::{password::SecretString, Error};
+
+/// Passwords stored encrypted on disk, unlocked with a passphrase or the
+/// content of a key file. Configurations refer to them by name, so that they
+/// never hold a password in clear text.
+#[derive(Clone, Default)]
+pub struct CredentialVault {
+    entries: BTreeMap<String, SecretString>,
+}
+
+impl std::fmt::Debug for CredentialVault {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.entries.keys()).finish()
+    }
+}
+
+/// How a vault is stored on disk. The key is derived from the secret with
+/// Argon2id and a random salt, and the entries are encrypted as JSON with
+/// AES-256-GCM; both salt and nonce change every time the vault is saved.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SealedVault {
+    version: u32,
+    salt: String,
+    nonce: String,
+    data: String,
+}
+
+impl CredentialVault {
+    const VERSION: u32 = 1;
+    const SALT_LEN: usize = 16;
+
+    /// Open the vault at `path` with `secret`, or an empty one if there is
+    /// none yet.
+    pub fn open(path: &Path, secret: &[u8]) -> crate::Result<CredentialVault> {
+        if !path.exists() {
+            log::debug!("no vault in {:?}, starting from an empty one", path);
+            return Ok(CredentialVault::default());
+        }
+        let error = |reason: &str| Error::Vault {
+            path: path.to_path_buf(),
+            reason: reason.to_string(),
+        };
+
+        let raw = std::fs::read_to_string(path)?;
+        let sealed: SealedVault = serde_json::from_str(&raw).map_err(|e| error(&e.to_string()))?;
+        if sealed.version != Self::VERSION {
+            return Err(error(&format!("unsupported version {}", sealed.version)));
+        }
+        let decode = |s: &str| BASE64.decode(s).map_err(|e| error(&e.to_string()));
+        let (salt, nonce, data) = (
+            decode(&sealed.salt)?,
+            decode(&sealed.nonce)?,
+            decode(&sealed.data)?,
+        );
+        if nonce.len() != 12 {
+            return Err(error("invalid nonce"));
+        }
+
+        let cipher = Self::cipher(secret, &salt).map_err(|e| error(&e))?;
+        let plain = cipher
+            .decrypt(Nonce::from_slice(&nonce), data.as_slice())
+            .map(Zeroizing::new)
+            .map_err(|_| error("wrong passphrase or key file"))?;
+        let entries = serde_json::from_slice(&plain).map_err(|e| error(&e.to_string()))?;
+        Ok(CredentialVault { entries })
+    }
+
+    /// Encrypt the vault with `secret` and write it to `path`, readable only
+    /// by the user.
+    pub fn save(&self, path: &Path, secret: &[u8]) -> crate::Result<()> {
+        let error = |reason: String| Error::Vault {
+            path: path.to_path_buf(),
+            reason,
+        };
+
+        let mut salt = [0u8; Self::SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let cipher = Self::cipher(secret, &salt).map_err(error)?;
+        let plain =
+            Zeroizing::new(serde_json::to_vec(&self.entries).map_err(std::io::Error::from)?);
+        let data = cipher
+            .encrypt(&nonce, plain.as_slice())
+            .map_err(|e| error(e.to_string()))?;
+        let sealed = SealedVault {
+            version: Self::VERSION,
+            salt: BASE64.encode(salt),
+            nonce: BASE64.encode(nonce),
+            data: BASE64.encode(data),
+        };
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        // A temporary file left behind by an interrupted save keeps whatever
+        // mode it had, so it is replaced by a new one.
+        let tmp = path.with_extension("tmp");
+        match std::fs::remove_file(&tmp) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(&tmp)?;
+        std::io::Write::write_all(
+            &mut file,
+            serde_json::to_string_pretty(&sealed)
+                .map_err(std::io::Error::from)?
+                .as_bytes(),
+        )?;
+        std::fs::rename(tmp, path)?;
+        Ok(())
+    }
+
+    fn cipher(secret: &[u8], salt: &[u8]) -> Result<Aes256Gcm, String> {
+        let mut key = Zeroizing::new([0u8; 32]);
+        argon2::Argon2::default()
+            .hash_password_into(secret, salt, key.as_mut())
+            .map_err(|e| e.to_string())?;
+        Aes256Gcm::new_from_slice(key.as_ref()).map_err(|e| e.to_string())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&SecretString> {
+        self.entries.get(name)
+    }
+
+    /// Store `password` under `name`, returning whether it was already there.
+    pub fn set(&mut self, name: &str, password: SecretString) -> bool {
+        self.entries.insert(name.to_string(), password).is_some()
+    }
+
+    /// Remove the entry `name`, returning whether there was one.
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.entries.remove(name).is_some()
+    }
+
+    /// The names of the stored entries, in alphabetical order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(String::as_str)
+    }
+}
@@ -0,0 +1,264 @@
+//! A local HTTP server for the fixtures, to test the HTTP middleware itself.
+
+use std::{
+    collections::{HashMap, HashSet},
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+pub const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+const LAST_MODIFIED: &str = "Mon, 02 Oct 2023 10:00:00 GMT";
+
+/// A request received by a [`FixtureServer`], with lowercase header names.
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub headers: HashMap<String, String>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// Paths whose next answer stops after this many bytes of the body.
+    cut: HashMap<String, usize>,
+    /// Paths answered with the login page to requests without a session.
+    behind_login: HashSet<String>,
+    /// The sessions given out by `POST /login` and not expired yet.
+    sessions: HashSet<String>,
+    logins: usize,
+    requests: Vec<Request>,
+}
+
+/// Serves the fixtures directory on a random local port: `GET /a/b` answers
+/// with the file `a/b`, honouring `Range` and `If-Range`, and `POST /login`
+/// logs in, setting a `session` cookie.
+#[derive(Debug, Clone)]
+pub struct FixtureServer {
+    base: String,
+    state: Arc<Mutex<State>>,
+}
+
+impl FixtureServer {
+    pub fn start() -> FixtureServer {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let server = FixtureServer {
+            base: format!("http://{}", listener.local_addr().unwrap()),
+            state: Arc::default(),
+        };
+
+        let state = server.state.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let state = state.clone();
+                std::thread::spawn(move || serve(stream, &state));
+            }
+        });
+        server
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base, path)
+    }
+
+    /// Drop the connection after `after` bytes of the body the next time
+    /// `path` is asked for.
+    pub fn cut_once(&self, path: &str, after: usize) {
+        self.state
+            .lock()
+            .unwrap()
+            .cut
+            .insert(path.to_string(), after);
+    }
+
+    /// Answer with the login page when `path` is asked for without a
+    /// session.
+    pub fn behind_login(&self, path: &str) {
+        self.state
+            .lock()
+            .unwrap()
+            .behind_login
+            .insert(path.to_string());
+    }
+
+    /// Forget every session, as if they all expired.
+    pub fn expire_sessions(&self) {
+        self.state.lock().unwrap().sessions.clear();
+    }
+
+    pub fn requests(&self) -> Vec<Request> {
+        self.state.lock().unwrap().requests.clone()
+    }
+}
+
+/// A login page where the login form comes after more than a chunk.
+pub fn login_page() -> String {
+    format!(
+        "<html><body>{}<div id=\"cvLogin\"><input name=\"tbLogin\"></div></body></html>",
+        " ".repeat(64 * 1024)
+    )
+}
+
+/// The ETag the server sends for `body`.
+pub fn etag(body: &[u8]) -> String {
+    let hash = body
+        .iter()
+        .fold(0u64, |h, &b| h.wrapping_mul(31).wrapping_add(u64::from(b)));
+    format!("\"{:x}-{}\"", hash, body.len())
+}
+
+fn serve(stream: TcpStream, state: &Mutex<State>) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut line = String::new();
+    if reader.read_line(&mut line).is_err() || line.is_empty() {
+        return;
+    }
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or_default().to_string();
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        match line.trim_end().split_once(':') {
+            Some((name, value)) => {
+                headers.insert(name.to_lowercase(), value.trim().to_string());
+            }
+            None => break,
+        }
+    }
+    let length = headers
+        .get("content-length")
+        .and_then(|l| l.parse().ok())
+        .unwrap_or(0);
+    reader.read_exact(&mut vec![0; length]).unwrap();
+
+    let request = Request {
+        method,
+        path,
+        headers,
+    };
+    let mut state = state.lock().unwrap();
+    state.requests.push(request.clone());
+    let path = request.path.split('?').next().unwrap_or_default();
+
+    if request.method == "POST" && path == "/login" {
+        state.logins += 1;
+        let session = format!("s{}", state.logins);
+        state.sessions.insert(session.clone());
+        drop(state);
+        return respond(
+            stream,
+            "200 OK",
+            "text/html",
+            &[(
+                "Set-Cookie".to_string(),
+                format!("session={}; Path=/; HttpOnly", session),
+            )],
+            b"<html>welcome</html>",
+            None,
+        );
+    }
+    let logged_in = request
+        .headers
+        .get("cookie")
+        .into_iter()
+        .flat_map(|c| c.split(';'))
+        .filter_map(|c| c.trim().strip_prefix("session="))
+        .any(|session| state.sessions.contains(session));
+    if state.behind_login.contains(path) && !logged_in {
+        drop(state);
+        return respond(
+            stream,
+            "200 OK",
+            "text/html",
+            &[],
+            login_page().as_bytes(),
+            None,
+        );
+    }
+    let cut = match request.method.as_str() {
+        "GET" => state.cut.remove(path),
+        _ => None,
+    };
+    drop(state);
+
+    let body = match std::fs::read(Path::new(FIXTURES).join(path.trim_start_matches('/'))) {
+        Ok(body) => body,
+        Err(_) => return respond(stream, "404 Not Found", "text/plain", &[], b"", None),
+    };
+    let content_type = match path.rsplit_once('.').map(|(_, ext)| ext) {
+        Some("html" | "aspx") => "text/html",
+        Some("m3u8") => "application/vnd.apple.mpegurl",
+        _ => "application/octet-stream",
+    };
+    let etag = etag(&body);
+    let mut extra = vec![
+        ("ETag".to_string(), etag.clone()),
+        ("Last-Modified".to_string(), LAST_MODIFIED.to_string()),
+        ("Accept-Ranges".to_string(), "bytes".to_string()),
+    ];
+    if request.method == "HEAD" {
+        return respond(stream, "200 OK", content_type, &extra, &body, Some(0));
+    }
+
+    let offset = request
+        .headers
+        .get("range")
+        .and_then(|r| r.strip_prefix("bytes="))
+        .and_then(|r| r.strip_suffix('-'))
+        .and_then(|r| r.parse::<usize>().ok())
+        .filter(|_| request.headers.get("if-range").is_none_or(|v| *v == etag))
+        .filter(|&offset| offset < body.len());
+    let (status, body) = match offset {
+        Some(offset) => {
+            extra.push((
+                "Content-Range".to_string(),
+                format!("bytes {}-{}/{}", offset, body.len() - 1, body.len()),
+            ));
+            ("206 Partial Content", &body[offset..])
+        }
+        None => ("200 OK", &body[..]),
+    };
+
+    respond(stream, status, content_type, &extra, body, cut);
+}
+
+/// Answer with `body`, sending it in two parts so that it arrives in more
+/// than one chunk, or only its first `cut` bytes (none for a HEAD).
+fn respond(
+    mut stream: TcpStream,
+    status: &str,
+    content_type: &str,
+    extra: &[(String, String)],
+    body: &[u8],
+    cut: Option<usize>,
+) {
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len(),
+        extra
+            .iter()
+            .map(|(k, v)| format!("{}: {}\r\n", k, v))
+            .collect::<String>()
+    );
+    if stream.write_all(head.as_bytes()).is_err() {
+        return;
+    }
+
+    let (first, rest) = body.split_at(cut.unwrap_or(body.len() / 2).min(body.len()));
+    let _ = stream.write_all(first);
+    let _ = stream.flush();
+    if cut.is_some() {
+        let _ = stream.shutdown(std::net::Shutdown::Both);
+        return;
+    }
+    std::thread::sleep(Duration::from_millis(20));
+    let _ = stream.write_all(rest);
+}
@@ -0,0 +1,419 @@
+use sebastian_core::ariel::{
+    forum::{ArielExportFormat, ArielForumThread},
+    map::ArielSitemap,
+    mware::fixture::FixtureArielMiddleware,
+    news,
+    page::{ArielPageData, ArielPageDataKind},
+    search::{ArielAccessFilter, ArielSearchFilter, ArielSearchSort},
+    tool::ArielToolKind,
+    tree::ArielSite,
+    ArielNavigator, ArielUserConfig,
+};
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+const HOME_URL: &str = "https://ariel.unimi.it/";
+const SITE_URL: &str = "https://fakecourse.ariel.ctu.unimi.it/v5/home/Default.aspx";
+const THREADS_URL: &str =
+    "https://fakecourse.ariel.ctu.unimi.it/v5/frm3/ThreadList.aspx?name=contenuti&id=1";
+
+fn navigator(config: ArielUserConfig) -> ArielNavigator {
+    ArielNavigator::with_middleware(FixtureArielMiddleware::with_root(config, FIXTURES))
+}
+
+async fn thread_data(nav: &ArielNavigator) -> Vec<ArielPageData> {
+    let mut data = nav
+        .page_from_url(THREADS_URL.to_string())
+        .await
+        .unwrap()
+        .get_data();
+    data.sort_by_key(|d| d.url.to_string());
+    data
+}
+
+async fn download(nav: &ArielNavigator, data: ArielPageData) -> Vec<u8> {
+    let dir = tempfile::tempdir().unwrap();
+    let (px, mut cx) = tokio::sync::mpsc::unbounded_channel();
+    let path = nav
+        .download(dir.path().to_string_lossy().to_string(), data, px)
+        .await
+        .unwrap();
+    assert!(path.starts_with(dir.path()));
+    let bytes = std::fs::read(path).unwrap();
+    let mut reported = 0;
+    while let Ok(size) = cx.try_recv() {
+        reported += size;
+    }
+    assert_eq!(reported, bytes.len() as u64);
+    bytes
+}
+
+#[tokio::test]
+async fn search_parses_title_pages() {
+    let pages = navigator(ArielUserConfig::default())
+        .search("course")
+        .await
+        .unwrap();
+
+    // Courses without a usable link are skipped.
+    assert_eq!(pages.len(), 3);
+    assert_eq!(pages[0].title, "Fake Course");
+    assert_eq!(pages[0].url.as_str(), SITE_URL);
+    assert_eq!(pages[0].holders.len(), 2);
+    assert_eq!(pages[0].holders[0].id, "mario-rossi");
+    assert_eq!(pages[0].holders[0].name, "Mario Rossi");
+    // Relative links are resolved against the search page.
+    assert_eq!(
+        pages[0].holders[1].url.as_str(),
+        "https://ariel.unimi.it/offerta/teacher/anna-bianchi"
+    );
+    assert_eq!(pages[0].code.as_deref(), Some("F1X-42"));
+    assert_eq!(pages[0].degree.as_deref(), Some("Informatica"));
+    assert_eq!(pages[0].academic_year.as_deref(), Some("2021/2022"));
+    assert!(pages[0].can_access);
+    assert_eq!(pages[1].title, "Closed Course");
+    assert_eq!(pages[1].academic_year.as_deref(), Some("2020/2021"));
+    assert_eq!(pages[1].code, None);
+    assert!(!pages[1].can_access);
+    assert_eq!(pages[2].title, "Relative Course");
+    assert_eq!(
+        pages[2].url.as_str(),
+        "https://ariel.unimi.it/offerta/corsi/relative-course"
+    );
+}
+
+#[tokio::test]
+async fn search_filters_and_sorts() {
+    let pages = navigator(ArielUserConfig::default())
+        .search("course")
+        .await
+        .unwrap();
+    let titles = |pages: &[_]| {
+        pages
+            .iter()
+            .map(|p: &sebastian_core::ariel::page::ArielTitlePage| p.title.clone())
+            .collect::<Vec<_>>()
+    };
+
+    let filter = ArielSearchFilter::default();
+    assert_eq!(titles(&filter.apply(pages.clone())), vec!["Fake Course"]);
+
+    let filter = ArielSearchFilter {
+        teacher: Some("verdi".to_string()),
+        access: ArielAccessFilter::Any,
+        ..Default::default()
+    };
+    assert_eq!(titles(&filter.apply(pages.clone())), vec!["Closed Course"]);
+
+    let filter = ArielSearchFilter {
+        year: Some("2021-2022".to_string()),
+        access: ArielAccessFilter::Any,
+        ..Default::default()
+    };
+    assert_eq!(titles(&filter.apply(pages.clone())), vec!["Fake Course"]);
+
+    let mut sorted = pages.clone();
+    ArielSearchSort::Year.sort(&mut sorted);
+    assert_eq!(
+        titles(&sorted),
+        vec!["Closed Course", "Fake Course", "Relative Course"]
+    );
+    ArielSearchSort::Code.sort(&mut sorted);
+    assert_eq!(
+        titles(&sorted),
+        vec!["Fake Course", "Closed Course", "Relative Course"]
+    );
+    ArielSearchSort::Teacher.sort(&mut sorted);
+    assert_eq!(
+        titles(&sorted),
+        vec!["Closed Course", "Fake Course", "Relative Course"]
+    );
+}
+
+#[tokio::test]
+async fn sitemap_decides_which_sites_are_crawled() {
+    let nav = navigator(ArielUserConfig::default());
+    let home = nav.page_from_url(HOME_URL.to_string()).await.unwrap();
+    assert_eq!(
+        home.get_children(nav.sitemap()),
+        vec![
+            SITE_URL,
+            "https://missingcourse.ariel.ctu.unimi.it/v5/home/Default.aspx"
+        ]
+    );
+
+    let sitemap = ArielSitemap {
+        site_hosts: vec!["fakecourse.ariel.ctu.unimi.it".to_string()],
+        ..ArielSitemap::default()
+    };
+    assert_eq!(home.get_children(&sitemap), vec![SITE_URL]);
+    assert!(!sitemap.is_site_url("https://notfakecourse.ariel.ctu.unimi.it/"));
+
+    let nav = navigator(ArielUserConfig {
+        sitemap,
+        ..ArielUserConfig::default()
+    });
+    let home = nav.page_from_url(HOME_URL.to_string()).await.unwrap();
+    let sites = nav.get_children(home).await;
+    assert_eq!(sites.len(), 1);
+    assert_eq!(sites[0].url, SITE_URL);
+}
+
+#[tokio::test]
+async fn get_children_walks_down_to_threads() {
+    let nav = navigator(ArielUserConfig::default());
+
+    let home = nav.page_from_url(HOME_URL.to_string()).await.unwrap();
+    assert_eq!(home.get_title(), "Ariel");
+    // The missing course has no fixture and is skipped.
+    let sites = nav.get_children(home).await;
+    assert_eq!(sites.len(), 1);
+    assert_eq!(sites[0].url, SITE_URL);
+    assert_eq!(sites[0].get_title(), "Fake Course");
+
+    let ambients = nav.get_children(sites.into_iter().next().unwrap()).await;
+    assert_eq!(ambients.len(), 4);
+    assert_eq!(ambients[0].get_title(), "Contenuti");
+    assert_eq!(ambients[1].get_title(), "Forum");
+    assert!(ambients[1].is_forum());
+    assert!(ambients[2].is_news());
+    assert!(ambients[3].is_calendar());
+
+    let threads = nav.get_children(ambients.into_iter().next().unwrap()).await;
+    assert_eq!(threads.len(), 1);
+    assert_eq!(threads[0].url, THREADS_URL);
+}
+
+#[tokio::test]
+async fn get_data_finds_files_and_streams() {
+    let data = thread_data(&navigator(ArielUserConfig::default())).await;
+
+    assert_eq!(data.len(), 4);
+    assert!(matches!(data[0].kind, ArielPageDataKind::Generic));
+    assert_eq!(data[0].name, "slides.pdf");
+    assert_eq!(data[0].from_site, "Fake Course");
+    assert_eq!(data[0].from_ambient, "Lezioni");
+    assert_eq!(data[0].from_thread, "Lezione 1");
+    assert_eq!(data[0].thread_id, "thread12");
+    // Another thread with the same title.
+    assert_eq!(data[1].name, "esercizi.pdf");
+    assert_eq!(data[1].from_thread, "Lezione 1");
+    assert_eq!(data[1].thread_id, "thread14");
+    assert!(matches!(data[2].kind, ArielPageDataKind::LessonStream));
+    assert_eq!(data[2].get_name(), "lezione1.mp4");
+    assert_eq!(data[2].thread_id, "thread12");
+    assert!(matches!(data[3].kind, ArielPageDataKind::LessonStream));
+    assert_eq!(data[3].from_thread, "Lezione 2");
+    assert_eq!(data[3].thread_id.len(), 16);
+}
+
+#[tokio::test]
+async fn get_messages_extracts_thread_text() {
+    let nav = navigator(ArielUserConfig::default());
+    let page = nav.page_from_url(THREADS_URL.to_string()).await.unwrap();
+    let messages = page.get_messages();
+
+    assert_eq!(messages.len(), 3);
+    assert_eq!(messages[0].title, "Lezione 1");
+    assert_eq!(messages[0].thread_id, "thread12");
+    assert_eq!(messages[0].author.as_deref(), Some("Mario Rossi"));
+    assert_eq!(messages[0].date.as_deref(), Some("01/03/2022 10:00"));
+    assert_eq!(messages[1].title, "Lezione 2");
+    assert_eq!(messages[1].author, None);
+    assert!(messages[1].body.is_empty());
+    assert_eq!(messages[1].thread_id.len(), 16);
+    assert_eq!(messages[2].title, "Lezione 1");
+    assert_eq!(messages[2].thread_id, "thread14");
+
+    let md = messages[0].to_markdown();
+    assert!(md.starts_with("# Lezione 1\n\n*Mario Rossi, 01/03/2022 10:00*\n\n"));
+    assert!(md.contains("**first**"));
+    assert!(md.contains("Exercise 1"));
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = messages[0].save(&dir.path().to_string_lossy()).unwrap();
+    assert!(path.ends_with("message_thread12.md"));
+    let data = thread_data(&nav).await;
+    assert_eq!(data[3].thread_id, messages[1].thread_id);
+    assert_eq!(
+        path.parent(),
+        data[0].get_path(&dir.path().to_string_lossy()).parent()
+    );
+    assert_eq!(std::fs::read_to_string(path).unwrap(), md);
+}
+
+#[tokio::test]
+async fn get_tools_classifies_every_tool() {
+    let nav = navigator(ArielUserConfig::default());
+    let site = nav.page_from_url(SITE_URL.to_string()).await.unwrap();
+    let tools = site.get_tools();
+
+    let kinds = tools.iter().map(|t| t.kind).collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        vec![
+            ArielToolKind::Contents,
+            ArielToolKind::Forum,
+            ArielToolKind::News,
+            ArielToolKind::Calendar,
+            ArielToolKind::Unknown,
+            ArielToolKind::Contents,
+            ArielToolKind::Unknown,
+        ]
+    );
+    assert_eq!(tools[4].name, "Wiki");
+    assert_eq!(
+        tools[4].url,
+        "https://fakecourse.ariel.ctu.unimi.it/v5/wiki/Pagina.aspx?toolName=wiki"
+    );
+    // Unknown tools, logging out included, and other hosts are not crawled.
+    assert_eq!(
+        site.get_children(nav.sitemap()),
+        tools[..4].iter().map(|t| t.url.clone()).collect::<Vec<_>>()
+    );
+    assert_eq!(ArielToolKind::classify("", "Avvisi"), ArielToolKind::News);
+}
+
+#[tokio::test]
+async fn get_site_builds_the_course_tree() {
+    let nav = navigator(ArielUserConfig::default());
+    let site = nav.get_site(SITE_URL.to_string()).await.unwrap();
+
+    assert_eq!(site.title, "Fake Course");
+    assert_eq!(site.tools.len(), 7);
+    assert_eq!(site.ambients.len(), 2);
+    assert_eq!(site.ambients[0].title, "Lezioni");
+    assert_eq!(site.ambients[0].url.as_str(), THREADS_URL);
+    let threads = &site.ambients[0].threads;
+    assert_eq!(threads.len(), 3);
+    assert_eq!(threads[0].title, "Lezione 1");
+    assert_eq!(threads[0].id, "thread12");
+    assert_eq!(threads[0].author.as_deref(), Some("Mario Rossi"));
+    assert_eq!(threads[0].attachments.len(), 2);
+    // Threads with the same title are kept apart.
+    assert_eq!(threads[1].title, "Lezione 1");
+    assert_eq!(threads[1].id, "thread14");
+    assert_eq!(threads[1].author.as_deref(), Some("Anna Bianchi"));
+    assert_eq!(threads[1].attachments.len(), 1);
+    assert_eq!(threads[2].title, "Lezione 2");
+    assert_eq!(threads[2].attachments.len(), 1);
+    assert_eq!(site.ambients[1].title, "Esami");
+    assert_eq!(site.data().len(), 5);
+
+    let json = serde_json::to_string(&site).unwrap();
+    assert_eq!(serde_json::from_str::<ArielSite>(&json).unwrap(), site);
+}
+
+#[tokio::test]
+async fn get_forum_collects_discussions() {
+    let nav = navigator(ArielUserConfig::default());
+    let threads = nav.get_forum(SITE_URL.to_string()).await.unwrap();
+
+    assert_eq!(threads.len(), 2);
+    let thread = &threads[0];
+    assert_eq!(thread.from_site, "Fake Course");
+    assert_eq!(thread.from_room, "Esami");
+    assert_eq!(thread.title, "Data dell'appello");
+    assert_eq!(thread.posts.len(), 2);
+    assert_eq!(thread.posts[0].author.as_deref(), Some("Anna Bianchi"));
+    assert_eq!(thread.posts[0].quote, None);
+    assert_eq!(thread.posts[1].author.as_deref(), Some("Mario Rossi"));
+    assert_eq!(
+        thread.posts[1].quote.as_deref(),
+        Some("<p>When is the exam?</p><p>And where?</p>")
+    );
+    assert_eq!(thread.posts[1].attachments.len(), 1);
+    assert_eq!(thread.posts[1].attachments[0].name, "calendario.pdf");
+
+    let md = thread.to_markdown();
+    assert!(md.starts_with("# Data dell'appello\n\n## Data dell'appello\n\n"));
+    assert!(md.contains("> When is the exam?\n>\n> And where?\n\n"));
+    assert!(md.contains("*June 10th*"));
+    assert!(md.contains("[calendario.pdf]("));
+
+    let dir = tempfile::tempdir().unwrap();
+    let base = dir.path().to_string_lossy();
+    let path = thread.save(&base, ArielExportFormat::Markdown).unwrap();
+    assert_eq!(std::fs::read_to_string(path).unwrap(), md);
+    let path = thread.save(&base, ArielExportFormat::Json).unwrap();
+    let json = std::fs::read_to_string(path).unwrap();
+    assert_eq!(
+        &serde_json::from_str::<ArielForumThread>(&json).unwrap(),
+        thread
+    );
+}
+
+#[tokio::test]
+async fn forum_threads_with_the_same_title_are_saved_apart() {
+    let nav = navigator(ArielUserConfig::default());
+    let threads = nav.get_forum(SITE_URL.to_string()).await.unwrap();
+
+    assert_eq!(threads[0].title, threads[1].title);
+    assert_ne!(threads[0].url, threads[1].url);
+    assert_eq!(threads[1].posts[0].author.as_deref(), Some("Luca Verdi"));
+
+    let dir = tempfile::tempdir().unwrap();
+    let base = dir.path().to_string_lossy();
+    for format in [ArielExportFormat::Markdown, ArielExportFormat::Json] {
+        let paths = threads
+            .iter()
+            .map(|t| t.save(&base, format).unwrap())
+            .collect::<Vec<_>>();
+        assert_ne!(paths[0], paths[1]);
+        assert_eq!(paths[0].parent(), paths[1].parent());
+        assert!(paths[0]
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("data_dell_appello_"));
+        assert!(std::fs::read_to_string(&paths[1])
+            .unwrap()
+            .contains("Luca Verdi"));
+    }
+}
+
+#[tokio::test]
+async fn get_news_lists_announcements_newest_first() {
+    let nav = navigator(ArielUserConfig::default());
+    let news = nav.get_news(HOME_URL.to_string()).await.unwrap();
+
+    assert_eq!(news.len(), 2);
+    assert_eq!(news[0].title, "Lezione sospesa");
+    assert_eq!(news[0].from_site, "Fake Course");
+    assert_eq!(news[0].published.as_deref(), Some("2022-03-01T12:30"));
+    assert_eq!(news[0].to_markdown(), "Tomorrow's lesson is **cancelled**.");
+    assert_eq!(news[1].title, "Benvenuti");
+
+    let since = news::parse_date("2022-02-20").unwrap();
+    assert!(news[0].is_since(&since));
+    assert!(!news[1].is_since(&since));
+    assert_eq!(nav.get_news(SITE_URL.to_string()).await.unwrap(), news);
+}
+
+#[test]
+fn parse_date_normalizes_ariel_dates() {
+    assert_eq!(
+        news::parse_date("1/3/2022 9.05").as_deref(),
+        Some("2022-03-01T09:05")
+    );
+    assert_eq!(
+        news::parse_date("2022-03-01").as_deref(),
+        Some("2022-03-01T00:00")
+    );
+    assert_eq!(news::parse_date("13/13/2022"), None);
+    assert_eq!(news::parse_date("31/02/2022"), None);
+    assert_eq!(news::parse_date("29/02/2024 25:00"), None);
+    assert_eq!(
+        news::parse_date("29/02/2024").as_deref(),
+        Some("2024-02-29T00:00")
+    );
+    assert_eq!(news::parse_date("yesterday"), None);
+}
+
+#[tokio::test]
+async fn download_generic_file() {
+    let nav = navigator(ArielUserConfig::default());
+    let data = thread_data(&nav).await;
+
+    assert_eq!(nav.get_size(&data[0]).await.unwrap(), 12);
+    assert_eq!(download(&nav, data[0].clone()).await, b"fake slides\n");
+}
@@ -0,0 +1 @@
+high 1
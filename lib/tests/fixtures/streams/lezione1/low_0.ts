@@ -0,0 +1 @@
+low 0
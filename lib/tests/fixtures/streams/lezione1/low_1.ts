@@ -0,0 +1 @@
+low 1
@@ -0,0 +1 @@
+幞QQ?{bw,>X
\ No newline at end of file
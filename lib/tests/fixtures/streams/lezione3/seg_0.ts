@@ -0,0 +1 @@
+k~Z;׾LnBb#jv
\ No newline at end of file
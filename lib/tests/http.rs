@@ -0,0 +1,391 @@
+mod common;
+
+use std::path::{Path, PathBuf};
+
+use common::FixtureServer;
+use sebastian_core::{
+    ariel::{
+        manifest::ArielManifest,
+        map::ArielSitemap,
+        mware::{http::HttpArielMiddleware, ArielMiddleware},
+        page::{ArielPageData, ArielPageDataKind},
+        queue::{ArielDownloadEvent, ArielDownloadQueue},
+        ArielNavigator, ArielStreamQuality, ArielUserConfig,
+    },
+    net::{NetworkConfig, RetryPolicy},
+    Error,
+};
+
+fn navigator(server: &FixtureServer, config: ArielUserConfig) -> ArielNavigator {
+    let config = ArielUserConfig {
+        sitemap: ArielSitemap {
+            login_url: server.url("/login"),
+            home_page_url: server.url("/ariel.unimi.it/index.html"),
+            ..ArielSitemap::default()
+        },
+        network: NetworkConfig {
+            retry: RetryPolicy {
+                initial_backoff_ms: 10,
+                ..RetryPolicy::default()
+            },
+            ..NetworkConfig::default()
+        },
+        ..config
+    };
+    ArielNavigator::with_middleware(HttpArielMiddleware::new(config).unwrap())
+}
+
+fn data(server: &FixtureServer, path: &str, kind: ArielPageDataKind) -> ArielPageData {
+    ArielPageData {
+        from_site: "Fake Course".to_string(),
+        from_ambient: "Materiali".to_string(),
+        from_thread: "Lezione 1".to_string(),
+        thread_id: "thread12".to_string(),
+        name: path.rsplit('/').next().unwrap().to_string(),
+        url: server.url(path).parse().unwrap(),
+        kind,
+    }
+}
+
+fn part_path(data: &ArielPageData, dir: &Path) -> PathBuf {
+    let mut path = data.get_path(&dir.to_string_lossy()).into_os_string();
+    path.push(".part");
+    PathBuf::from(path)
+}
+
+/// Download `data` into `dir`, returning what was written along with the
+/// progress reported.
+async fn download(
+    nav: &ArielNavigator,
+    data: ArielPageData,
+    dir: &Path,
+) -> sebastian_core::Result<(Vec<u8>, u64)> {
+    let (px, mut cx) = tokio::sync::mpsc::unbounded_channel();
+    let path = nav
+        .download(dir.to_string_lossy().to_string(), data, px)
+        .await?;
+    assert!(path.starts_with(dir));
+    let bytes = std::fs::read(path).unwrap();
+    let mut reported = 0;
+    while let Ok(size) = cx.try_recv() {
+        reported += size;
+    }
+    Ok((bytes, reported))
+}
+
+#[tokio::test]
+async fn streams_honour_quality() {
+    let server = FixtureServer::start();
+    let dir = tempfile::tempdir().unwrap();
+    let stream = data(
+        &server,
+        "/streams/lezione1/manifest.m3u8",
+        ArielPageDataKind::LessonStream,
+    );
+
+    let nav = navigator(&server, ArielUserConfig::default());
+    // 8 seconds at 2000000 bps.
+    assert_eq!(nav.get_size(&stream).await.unwrap(), 2_000_000);
+    assert_eq!(
+        download(&nav, stream.clone(), dir.path()).await.unwrap(),
+        (b"high 0\nhigh 1\n".to_vec(), 14)
+    );
+
+    let nav = navigator(
+        &server,
+        ArielUserConfig {
+            stream_quality: ArielStreamQuality::MaxHeight(480),
+            ..ArielUserConfig::default()
+        },
+    );
+    assert_eq!(
+        download(&nav, stream, dir.path()).await.unwrap(),
+        (b"low 0\nlow 1\n".to_vec(), 12)
+    );
+}
+
+#[tokio::test]
+async fn encrypted_streams_are_decrypted() {
+    let server = FixtureServer::start();
+    let dir = tempfile::tempdir().unwrap();
+    let nav = navigator(&server, ArielUserConfig::default());
+
+    let stream = data(
+        &server,
+        "/streams/lezione2/manifest.m3u8",
+        ArielPageDataKind::LessonStream,
+    );
+    // Progress counts the encrypted segments, padding included.
+    let (bytes, _) = download(&nav, stream, dir.path()).await.unwrap();
+    assert_eq!(
+        bytes,
+        b"first encrypted segment\nsecond encrypted segment\nplain segment\n"
+    );
+
+    for (path, reason) in [
+        ("/streams/lezione3/manifest.m3u8", "16 bytes"),
+        ("/streams/lezione3/no_uri.m3u8", "missing key URI"),
+    ] {
+        let stream = data(&server, path, ArielPageDataKind::LessonStream);
+        match download(&nav, stream, dir.path()).await {
+            Err(Error::Stream { reason: r, .. }) => assert!(r.contains(reason), "{}", r),
+            res => panic!("unexpected {:?}", res),
+        }
+    }
+}
+
+#[tokio::test]
+async fn interrupted_downloads_resume_from_the_part_file() {
+    let server = FixtureServer::start();
+    let dir = tempfile::tempdir().unwrap();
+    let nav = navigator(&server, ArielUserConfig::default());
+    let notes = data(&server, "/files/notes.pdf", ArielPageDataKind::Generic);
+    let content = std::fs::read(format!("{}/files/notes.pdf", common::FIXTURES)).unwrap();
+
+    let part = part_path(&notes, dir.path());
+    std::fs::create_dir_all(part.parent().unwrap()).unwrap();
+    std::fs::write(&part, &content[..10]).unwrap();
+    let mut validator = part.clone().into_os_string();
+    validator.push(".validator");
+    std::fs::write(&validator, common::etag(&content)).unwrap();
+
+    assert_eq!(
+        download(&nav, notes.clone(), dir.path()).await.unwrap(),
+        (content.clone(), content.len() as u64)
+    );
+    let get = server.requests().pop().unwrap();
+    assert_eq!(get.headers["range"], "bytes=10-");
+    assert_eq!(get.headers["if-range"], common::etag(&content));
+    assert!(!part.exists());
+    assert!(!Path::new(&validator).exists());
+
+    // A partial download of another version of the file, or one larger than
+    // the file, is started over.
+    for (partial, etag) in [
+        (&b"stale bytes"[..], "\"old\"".to_string()),
+        (&[b'x'; 100][..], common::etag(&content)),
+    ] {
+        std::fs::write(&part, partial).unwrap();
+        std::fs::write(&validator, etag).unwrap();
+        assert_eq!(
+            download(&nav, notes.clone(), dir.path()).await.unwrap(),
+            (content.clone(), content.len() as u64)
+        );
+        assert!(!server
+            .requests()
+            .pop()
+            .unwrap()
+            .headers
+            .contains_key("range"));
+    }
+}
+
+#[tokio::test]
+async fn dropped_connections_are_resumed() {
+    let server = FixtureServer::start();
+    let dir = tempfile::tempdir().unwrap();
+    let nav = navigator(&server, ArielUserConfig::default());
+    let notes = data(&server, "/files/notes.pdf", ArielPageDataKind::Generic);
+    let content = std::fs::read(format!("{}/files/notes.pdf", common::FIXTURES)).unwrap();
+
+    server.cut_once("/files/notes.pdf", 10);
+    assert_eq!(
+        download(&nav, notes, dir.path()).await.unwrap(),
+        (content.clone(), content.len() as u64)
+    );
+    assert_eq!(nav.retries(), 1);
+    assert_eq!(
+        server.requests().pop().unwrap().headers["range"],
+        "bytes=10-"
+    );
+
+    // Segments are fetched again from the start, without counting twice the
+    // bytes read before the connection dropped.
+    server.cut_once("/streams/lezione1/high_1.ts", 3);
+    let stream = data(
+        &server,
+        "/streams/lezione1/manifest.m3u8",
+        ArielPageDataKind::LessonStream,
+    );
+    assert_eq!(
+        download(&nav, stream, dir.path()).await.unwrap(),
+        (b"high 0\nhigh 1\n".to_vec(), 14)
+    );
+    assert_eq!(nav.retries(), 2);
+}
+
+#[tokio::test]
+async fn login_pages_are_never_saved_as_files() {
+    let server = FixtureServer::start();
+    let dir = tempfile::tempdir().unwrap();
+    let nav = navigator(&server, ArielUserConfig::default());
+
+    server.behind_login("/files/slides.pdf");
+    let slides = data(&server, "/files/slides.pdf", ArielPageDataKind::Generic);
+    assert_eq!(
+        download(&nav, slides, dir.path()).await.unwrap(),
+        (b"fake slides\n".to_vec(), 12)
+    );
+    assert!(server
+        .requests()
+        .iter()
+        .any(|r| r.method == "POST" && r.path == "/login"));
+}
+
+#[tokio::test]
+async fn saved_sessions_are_reused_until_they_expire() {
+    let server = FixtureServer::start();
+    let dir = tempfile::tempdir().unwrap();
+    let session_path = dir.path().join("session.json");
+    let config = || ArielUserConfig {
+        session_path: Some(session_path.clone()),
+        ..ArielUserConfig::default()
+    };
+    let logins = || {
+        server
+            .requests()
+            .iter()
+            .filter(|r| r.method == "POST" && r.path == "/login")
+            .count()
+    };
+    server.behind_login("/ariel.unimi.it/index.html");
+
+    navigator(&server, config()).login().await.unwrap();
+    assert_eq!(logins(), 1);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&session_path)
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    // The next run checks the saved session instead of logging in again.
+    navigator(&server, config()).login().await.unwrap();
+    assert_eq!(logins(), 1);
+    let check = server.requests().pop().unwrap();
+    assert_eq!(check.path, "/ariel.unimi.it/index.html");
+    assert!(check.headers["cookie"].contains("session=s1"));
+
+    // An expired session falls back to a fresh login, saved in its place.
+    server.expire_sessions();
+    navigator(&server, config()).login().await.unwrap();
+    assert_eq!(logins(), 2);
+    navigator(&server, config()).login().await.unwrap();
+    assert_eq!(logins(), 2);
+    assert!(server.requests().pop().unwrap().headers["cookie"].contains("session=s2"));
+
+    // Only the names of the cookies are shown.
+    let debug = format!("{:?}", HttpArielMiddleware::new(config()).unwrap());
+    assert!(debug.contains("session=***"), "{}", debug);
+    assert!(!debug.contains("session=s2"), "{}", debug);
+}
+
+#[tokio::test]
+async fn download_queue_fetches_everything() {
+    let server = FixtureServer::start();
+    let dir = tempfile::tempdir().unwrap();
+    let nav = navigator(&server, ArielUserConfig::default());
+    let data = vec![
+        data(&server, "/files/slides.pdf", ArielPageDataKind::Generic),
+        data(
+            &server,
+            "/streams/lezione1/manifest.m3u8",
+            ArielPageDataKind::LessonStream,
+        ),
+        data(
+            &server,
+            "/streams/lezione2/manifest.m3u8",
+            ArielPageDataKind::LessonStream,
+        ),
+    ];
+    let (px, cx) = std::sync::mpsc::channel();
+
+    let results = ArielDownloadQueue::new(nav, 2)
+        .run(
+            dir.path().to_string_lossy().to_string(),
+            data.into_iter().map(|d| (d, None)).collect(),
+            px,
+        )
+        .await;
+
+    assert_eq!(results.len(), 3);
+    for (_, res) in results {
+        assert!(res.unwrap().exists());
+    }
+    assert!(cx.iter().count() >= 3 * 3);
+}
+
+#[tokio::test]
+async fn synced_streams_are_skipped_the_second_time() {
+    let server = FixtureServer::start();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().to_string_lossy().to_string();
+    let queue = ArielDownloadQueue::new(navigator(&server, ArielUserConfig::default()), 2);
+    let data = vec![
+        data(
+            &server,
+            "/streams/lezione1/manifest.m3u8",
+            ArielPageDataKind::LessonStream,
+        ),
+        data(&server, "/files/slides.pdf", ArielPageDataKind::Generic),
+    ];
+
+    let mut manifest = ArielManifest::default();
+    let changed = queue.changed(&manifest, data.clone()).await;
+    assert_eq!(changed.len(), 2);
+    assert!(changed.iter().all(|(_, version)| version.is_some()));
+
+    let (px, cx) = std::sync::mpsc::channel();
+    queue.run(path.clone(), changed, px).await;
+    for event in cx.iter() {
+        if let ArielDownloadEvent::Finished {
+            index,
+            path,
+            version,
+        } = event
+        {
+            let size = std::fs::metadata(&path).unwrap().len();
+            manifest.record(&data[index], path, size, version);
+        }
+    }
+
+    // The size of a stream is an estimate, far from what was written.
+    assert!(queue.changed(&manifest, data.clone()).await.is_empty());
+
+    // The versions found are not asked for again, only by the download.
+    let heads = || {
+        server
+            .requests()
+            .iter()
+            .filter(|r| r.method == "HEAD" && r.path == "/files/notes.pdf")
+            .count()
+    };
+    let notes = ArielPageData {
+        url: server.url("/files/notes.pdf").parse().unwrap(),
+        name: "notes.pdf".to_string(),
+        ..data[1].clone()
+    };
+    let (px, _cx) = std::sync::mpsc::channel();
+    let changed = queue.changed(&manifest, vec![notes]).await;
+    assert_eq!(heads(), 1);
+    queue.run(path, changed, px).await;
+    assert_eq!(heads(), 2);
+}
+
+#[tokio::test]
+async fn unknown_versions_count_as_changed() {
+    let server = FixtureServer::start();
+    let queue = ArielDownloadQueue::new(navigator(&server, ArielUserConfig::default()), 2);
+    let data = vec![
+        data(&server, "/files/missing.pdf", ArielPageDataKind::Generic),
+        data(&server, "/files/slides.pdf", ArielPageDataKind::Generic),
+    ];
+
+    let changed = queue.changed(&ArielManifest::default(), data).await;
+    assert_eq!(changed.len(), 2);
+    assert!(changed[0].1.is_none());
+    assert!(changed[1].1.is_some());
+}
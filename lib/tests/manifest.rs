@@ -0,0 +1,60 @@
+use sebastian_core::ariel::{
+    manifest::{ArielManifest, ArielRemoteVersion},
+    page::{ArielPageData, ArielPageDataKind},
+};
+
+#[test]
+fn manifest_notices_changes_by_etag_then_date_then_size() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("slides.pdf");
+    std::fs::write(&path, b"fake slides\n").unwrap();
+    let data = ArielPageData {
+        from_site: "site".to_string(),
+        from_ambient: "ambient".to_string(),
+        from_thread: "thread".to_string(),
+        thread_id: "thread1".to_string(),
+        name: "slides.pdf".to_string(),
+        url: "https://fakecourse.ariel.ctu.unimi.it/slides.pdf"
+            .parse()
+            .unwrap(),
+        kind: ArielPageDataKind::Generic,
+    };
+    let version = ArielRemoteVersion {
+        size: 12,
+        etag: Some("\"v1\"".to_string()),
+        last_modified: Some("Mon, 02 Oct 2023 10:00:00 GMT".to_string()),
+    };
+
+    let mut manifest = ArielManifest::default();
+    assert!(!manifest.is_up_to_date(&data, &version));
+    manifest.record(&data, path.clone(), 12, version.clone());
+    manifest.save(dir.path()).unwrap();
+    let manifest = ArielManifest::load(dir.path()).unwrap();
+    assert!(manifest.is_up_to_date(&data, &version));
+
+    // Same size, but the server says it changed.
+    let new_etag = ArielRemoteVersion {
+        etag: Some("\"v2\"".to_string()),
+        ..version.clone()
+    };
+    assert!(!manifest.is_up_to_date(&data, &new_etag));
+    let new_date = ArielRemoteVersion {
+        etag: None,
+        last_modified: Some("Tue, 03 Oct 2023 10:00:00 GMT".to_string()),
+        ..version.clone()
+    };
+    assert!(!manifest.is_up_to_date(&data, &new_date));
+    let size_only = ArielRemoteVersion {
+        size: 12,
+        ..ArielRemoteVersion::default()
+    };
+    assert!(manifest.is_up_to_date(&data, &size_only));
+    let new_size = ArielRemoteVersion {
+        size: 13,
+        ..ArielRemoteVersion::default()
+    };
+    assert!(!manifest.is_up_to_date(&data, &new_size));
+
+    std::fs::write(&path, b"edited\n").unwrap();
+    assert!(!manifest.is_up_to_date(&data, &version));
+}
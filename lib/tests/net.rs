@@ -0,0 +1,182 @@
+use std::time::{Duration, Instant};
+
+use sebastian_core::{
+    ariel::{
+        mware::{http::HttpArielMiddleware, ArielMiddleware},
+        ArielUserConfig,
+    },
+    net::{NetworkConfig, RateLimiter, RetryPolicy},
+    Error,
+};
+
+fn middleware(network: NetworkConfig) -> sebastian_core::Result<HttpArielMiddleware> {
+    HttpArielMiddleware::new(ArielUserConfig {
+        network,
+        ..ArielUserConfig::default()
+    })
+}
+
+#[test]
+fn invalid_network_settings_are_errors() {
+    let dir = tempfile::tempdir().unwrap();
+    let not_pem = dir.path().join("ca.pem");
+    std::fs::write(&not_pem, "not a certificate").unwrap();
+
+    let invalid = [
+        NetworkConfig {
+            proxy: Some("not a proxy".to_string()),
+            ..NetworkConfig::default()
+        },
+        NetworkConfig {
+            ca_bundle: Some(dir.path().join("missing.pem")),
+            ..NetworkConfig::default()
+        },
+        NetworkConfig {
+            ca_bundle: Some(not_pem),
+            ..NetworkConfig::default()
+        },
+    ];
+    for network in invalid {
+        assert!(matches!(
+            middleware(network),
+            Err(Error::NetworkSettings(_))
+        ));
+    }
+
+    let valid = NetworkConfig {
+        proxy: Some("socks5://127.0.0.1:1080".to_string()),
+        connect_timeout: Some(5),
+        read_timeout: Some(30),
+        user_agent: Some("test".to_string()),
+        ..NetworkConfig::default()
+    };
+    assert!(middleware(valid).is_ok());
+}
+
+#[tokio::test]
+async fn silent_servers_time_out() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        let _connections: Vec<_> = listener.incoming().collect();
+    });
+
+    let mw = middleware(NetworkConfig {
+        read_timeout: Some(1),
+        retry: RetryPolicy {
+            max_attempts: 1,
+            ..RetryPolicy::default()
+        },
+        ..NetworkConfig::default()
+    })
+    .unwrap();
+    assert!(matches!(
+        mw.get(url).await,
+        Err(Error::Timeout { secs: 1, .. })
+    ));
+}
+
+#[tokio::test]
+async fn overloaded_servers_are_retried() {
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        let answers = [
+            "HTTP/1.1 503 Service Unavailable\r\nRetry-After: 0\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok",
+        ];
+        for (mut stream, answer) in listener.incoming().flatten().zip(answers) {
+            let _ = stream.read(&mut [0; 1024]);
+            stream.write_all(answer.as_bytes()).unwrap();
+        }
+    });
+
+    let mw = middleware(NetworkConfig::default()).unwrap();
+    let (_, body) = mw.get(url).await.unwrap();
+    assert_eq!(body, "ok");
+    assert_eq!(mw.retries(), 1);
+}
+
+#[tokio::test]
+async fn waiting_to_retry_lets_other_requests_through() {
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        let mut answers = ["HTTP/1.1 503 Service Unavailable\r\nRetry-After: 1\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"].into_iter();
+        for mut stream in listener.incoming().flatten() {
+            let _ = stream.read(&mut [0; 1024]);
+            let answer = answers
+                .next()
+                .unwrap_or("HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok");
+            stream.write_all(answer.as_bytes()).unwrap();
+        }
+    });
+
+    let mw = middleware(NetworkConfig {
+        max_concurrent_requests: Some(1),
+        ..NetworkConfig::default()
+    })
+    .unwrap();
+    let start = Instant::now();
+    let other = async {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let res = mw.get(url.clone()).await;
+        (res, start.elapsed())
+    };
+    let (retried, (other, elapsed)) = tokio::join!(mw.get(url.clone()), other);
+    assert_eq!(retried.unwrap().1, "ok");
+    assert_eq!(other.unwrap().1, "ok");
+    assert!(elapsed < Duration::from_secs(1), "{:?}", elapsed);
+}
+
+#[test]
+fn backoff_grows_up_to_the_limit() {
+    let policy = RetryPolicy {
+        initial_backoff_ms: 100,
+        max_backoff_ms: 1_000,
+        ..RetryPolicy::default()
+    };
+    for (attempt, full) in [(1, 100), (2, 200), (3, 400), (5, 1_000), (40, 1_000)] {
+        let backoff = policy.backoff(attempt).as_millis() as u64;
+        assert!((full / 2..=full).contains(&backoff), "{}", backoff);
+    }
+    assert!(policy.retries_status(503));
+    assert!(!policy.retries_status(404));
+}
+
+#[tokio::test]
+async fn rate_limiter_keeps_within_limits() {
+    let limiter = RateLimiter::new(&NetworkConfig {
+        requests_per_second: Some(20),
+        max_concurrent_requests: Some(1),
+        max_download_rate: Some(10_000),
+        ..NetworkConfig::default()
+    });
+
+    let start = Instant::now();
+    for _ in 0..5 {
+        limiter.request().await;
+    }
+    assert!(start.elapsed() >= Duration::from_millis(200));
+
+    let turn = limiter.request().await;
+    assert!(
+        tokio::time::timeout(Duration::from_millis(100), limiter.request())
+            .await
+            .is_err()
+    );
+    drop(turn);
+    assert!(limiter.request().await.is_some());
+
+    let start = Instant::now();
+    limiter.downloaded(2_000).await;
+    limiter.downloaded(2_000).await;
+    assert!(start.elapsed() >= Duration::from_millis(400));
+
+    let unlimited = RateLimiter::new(&NetworkConfig::default());
+    assert!(unlimited.request().await.is_none());
+}
@@ -0,0 +1,60 @@
+use sebastian_core::{
+    password::{redact_form, PasswordSource, SecretString},
+    Error,
+};
+
+#[test]
+fn password_sources_parse_and_resolve() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("password");
+    std::fs::write(&file, "from a file\n").unwrap();
+    std::env::set_var("SEBASTIAN_TEST_PASSWORD", "from the environment");
+
+    let sources = [
+        ("command:echo from a command".to_string(), "from a command"),
+        (
+            "env:SEBASTIAN_TEST_PASSWORD".to_string(),
+            "from the environment",
+        ),
+        (format!("file:{}", file.display()), "from a file"),
+    ];
+    for (source, password) in sources {
+        let parsed: PasswordSource = source.parse().unwrap();
+        assert_eq!(parsed.to_string(), source);
+        assert_eq!(parsed.resolve().unwrap().expose(), password);
+    }
+
+    assert!(matches!(
+        "keyring:unimi".parse::<PasswordSource>(),
+        Err(Error::InvalidValue { .. })
+    ));
+    match PasswordSource::Command("echo locked >&2; exit 1".to_string()).resolve() {
+        Err(Error::PasswordSource { reason, .. }) => assert!(reason.ends_with(": locked")),
+        res => panic!("unexpected {:?}", res),
+    }
+    let missing = dir.path().join("missing");
+    match PasswordSource::File(missing.clone()).resolve() {
+        Err(e @ Error::PasswordSource { .. }) => {
+            assert!(e.to_string().contains(&*missing.to_string_lossy()))
+        }
+        res => panic!("unexpected {:?}", res),
+    }
+    assert!(matches!(
+        PasswordSource::Env("SEBASTIAN_TEST_UNSET".to_string()).resolve(),
+        Err(Error::PasswordSource { .. })
+    ));
+}
+
+#[test]
+fn secrets_are_never_printed() {
+    let secret = SecretString::from("hunter2");
+    assert_eq!(format!("{}", secret), "***");
+    assert_eq!(format!("{:?}", Some(secret.clone())), "Some(***)");
+    assert_eq!(secret.expose(), "hunter2");
+
+    let form = vec![
+        ("tbLogin".to_string(), "me".to_string()),
+        ("tbPassword".to_string(), "hunter2".to_string()),
+    ];
+    assert_eq!(redact_form(&form), "tbLogin=***&tbPassword=***");
+}
@@ -0,0 +1,52 @@
+use sebastian_core::{password::SecretString, vault::CredentialVault, Error};
+
+#[test]
+fn vault_round_trips_with_the_right_secret_only() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("credentials.vault");
+
+    let mut vault = CredentialVault::open(&path, b"correct horse").unwrap();
+    assert_eq!(vault.names().count(), 0);
+    assert!(!vault.set("ariel", "hunter2".into()));
+    assert!(!vault.set("unimia", "swordfish".into()));
+    vault.save(&path, b"correct horse").unwrap();
+
+    let raw = std::fs::read_to_string(&path).unwrap();
+    assert!(!raw.contains("hunter2"));
+    assert!(!format!("{:?}", vault).contains("hunter2"));
+
+    let mut vault = CredentialVault::open(&path, b"correct horse").unwrap();
+    assert_eq!(
+        vault.get("ariel").map(SecretString::expose),
+        Some("hunter2")
+    );
+    assert_eq!(vault.names().collect::<Vec<_>>(), vec!["ariel", "unimia"]);
+    assert!(vault.remove("unimia"));
+    assert!(!vault.remove("unimia"));
+
+    assert!(matches!(
+        CredentialVault::open(&path, b"battery staple"),
+        Err(Error::Vault { .. })
+    ));
+}
+
+#[cfg(unix)]
+#[test]
+fn vault_is_private_even_over_a_stale_temporary_file() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("credentials.vault");
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, "left by an interrupted save").unwrap();
+    std::fs::set_permissions(&tmp, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+    let mut vault = CredentialVault::open(&path, b"correct horse").unwrap();
+    vault.set("ariel", "hunter2".into());
+    vault.save(&path, b"correct horse").unwrap();
+
+    let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+    assert!(!tmp.exists());
+    assert!(CredentialVault::open(&path, b"correct horse").is_ok());
+}